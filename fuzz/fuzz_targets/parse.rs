@@ -0,0 +1,7 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+extern crate dns;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = dns::parse(data);
+});
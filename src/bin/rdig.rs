@@ -6,7 +6,10 @@ extern crate serde_json;
 use dns::DnsMessageBuilder;
 use dns::Question;
 use dns::{Qtype, Qclass};
+use dns::Name;
 use dns::parse;
+use dns::{DnsRecord, Edns, EdnsOption, ClientSubnet};
+use std::cmp;
 use std::net::UdpSocket;
 use clap::{App, Arg};
 
@@ -20,28 +23,58 @@ fn main() {
             .long("recurse")
             .help("Whether recursion is desired")
             .default_value("true"))
+        .arg(Arg::with_name("bufsize")
+            .short("b")
+            .long("bufsize")
+            .help("The UDP payload size advertised with EDNS")
+            .default_value("1232"))
         .arg(Arg::with_name("address")
             .index(1)
             .help("The address to dig")
             .required(true))
+        .arg(Arg::with_name("options")
+            .index(2)
+            .multiple(true)
+            .help("Query options, e.g. +subnet=192.0.2.0/24"))
         .get_matches();
 
-    let bytes = DnsMessageBuilder::new()
+    let address = value_t_or_exit!(matches, "address", String);
+    let name = match Name::new(&address) {
+        Ok(name) => name,
+        Err(e) => {
+            eprintln!("Error: {:?}: {}", e.kind, &e.msg);
+            std::process::exit(1);
+        }
+    };
+
+    let bufsize = value_t_or_exit!(matches, "bufsize", u16);
+    let mut edns = Edns::new(bufsize);
+    for option in matches.values_of("options").into_iter().flatten() {
+        if let Some(value) = option.strip_prefix("+subnet=") {
+            match value.parse::<ClientSubnet>() {
+                Ok(subnet) => edns.options.push(EdnsOption::ClientSubnet(subnet)),
+                Err(e) => {
+                    eprintln!("Error: {:?}: {}", e.kind, &e.msg);
+                    std::process::exit(1);
+                }
+            }
+        } else {
+            eprintln!("Error: Unknown query option {}", option);
+            std::process::exit(1);
+        }
+    }
+
+    let answer = DnsMessageBuilder::new()
         .add_question(Question::new(
-            &value_t_or_exit!(matches, "address", String),
+            name,
             Qtype::A,
             Qclass::IN))
         .with_rd(value_t_or_exit!(matches, "recurse", bool))
-        .build();
+        .with_edns(edns)
+        .build()
+        .and_then(|bytes| query(&bytes, bufsize as usize));
 
-    let mut socket = UdpSocket::bind("0.0.0.0:34000")
-        .expect("Could not bind to 0.0.0.0:34000");
-    socket.send_to(&bytes, "8.8.8.8:53")
-        .expect("Failed to send packet to 8.8.8.8:53");
-    let mut buffer = [0u8; 512];
-    socket.recv(&mut buffer);
-
-    match parse(&buffer) {
+    match answer {
         Ok(answer) => {
             let json = serde_json::to_string_pretty(&answer).unwrap();
             println!("{}", json);
@@ -52,3 +85,17 @@ fn main() {
         }
     };
 }
+
+fn query(bytes: &[u8], bufsize: usize) -> dns::Result<'static, DnsRecord<'static>> {
+    let socket = UdpSocket::bind("0.0.0.0:34000")
+        .expect("Could not bind to 0.0.0.0:34000");
+    socket.send_to(bytes, "8.8.8.8:53")
+        .expect("Failed to send packet to 8.8.8.8:53");
+    let mut buffer = vec![0u8; cmp::max(bufsize, 512)];
+    let len = socket.recv(&mut buffer)
+        .expect("Failed to receive answer from 8.8.8.8:53");
+
+    parse(&buffer[..len])
+        .map(|answer| answer.into_owned())
+        .map_err(|e| e.into_owned())
+}
@@ -1,13 +1,24 @@
 mod question;
+mod truncation;
 
 pub use self::question::Question;
+pub use self::truncation::Omissions;
 
 use rand::{Rng, thread_rng};
 use ::enums::*;
+use ::errors::Result;
 use ::labels::*;
+use ::dns_record::Record;
+use ::edns::Edns;
+use ::name::Name;
 use utils::{append_u16, write_u16};
+use self::truncation::rrsets;
+use std::cmp;
 use std::collections::HashMap;
 
+/// The maximum size of a message sent over UDP without EDNS.
+pub const MAX_UDP_SIZE: usize = 512;
+
 #[derive(Debug)]
 pub struct DnsMessageBuilder<'a> {
     id: u16,
@@ -16,9 +27,16 @@ pub struct DnsMessageBuilder<'a> {
     aa: bool,
     rd: bool,
     ra: bool,
-    z: u8,
+    z: bool,
+    ad: bool,
+    cd: bool,
     rcode: RCODE,
+    max_size: Option<usize>,
+    edns: Option<Edns<'a>>,
     questions: Vec<Question<'a>>,
+    answers: Vec<Record<'a>>,
+    authorities: Vec<Record<'a>>,
+    additionals: Vec<Record<'a>>,
 }
 
 impl<'a> DnsMessageBuilder<'a> {
@@ -30,9 +48,16 @@ impl<'a> DnsMessageBuilder<'a> {
             aa: false,
             rd: true,
             ra: false,
-            z: 0,
+            z: false,
+            ad: false,
+            cd: false,
             rcode: RCODE::Ok,
+            max_size: None,
+            edns: None,
             questions: vec![],
+            answers: vec![],
+            authorities: vec![],
+            additionals: vec![],
         }
     }
 
@@ -66,64 +91,204 @@ impl<'a> DnsMessageBuilder<'a> {
         self
     }
 
-    /// Set the message's dnssec (z) bits. Only the three
-    /// least significant bits of `val` will be taken into account, i.e.
-    /// val should be a right-alined byte.
-    pub fn with_dnssec_bits(mut self, val: u8) -> Self {
-        self.z = val & 0b_0000_0111;
+    /// Set the reserved Z bit. Only useful for testing how peers
+    /// handle it, as RFC 1035 requires it to be zero.
+    pub fn with_z(mut self, val: bool) -> Self {
+        self.z = val;
+        self
+    }
+
+    pub fn with_ad(mut self, val: bool) -> Self {
+        self.ad = val;
         self
     }
 
+    pub fn with_cd(mut self, val: bool) -> Self {
+        self.cd = val;
+        self
+    }
+
+    /// Set the message's RCODE. The upper eight bits of extended RCODEs
+    /// such as `BadCookie` are carried by the OPT record, so they are
+    /// lost without `with_edns`.
     pub fn with_rcode(mut self, val: RCODE) -> Self {
         self.rcode = val;
         self
     }
 
+    /// Limit the size of the built message. By default, this is the
+    /// UDP payload size given by `with_edns`, or 512 bytes without
+    /// EDNS. Records which don't fit are left out as described in
+    /// `build_with_omissions`.
+    pub fn with_max_size(mut self, val: usize) -> Self {
+        self.max_size = Some(val);
+        self
+    }
+
+    /// Add an OPT record to the additional section. Its extended RCODE
+    /// is replaced by the upper bits of the message's RCODE.
+    pub fn with_edns(mut self, edns: Edns<'a>) -> Self {
+        self.edns = Some(edns);
+        self
+    }
+
     pub fn add_question(mut self, question: Question<'a>) -> Self {
         self.questions.push(question);
         self
     }
 
-    pub fn build(self) -> Vec<u8> {
+    pub fn add_answer(mut self, record: Record<'a>) -> Self {
+        self.answers.push(record);
+        self
+    }
+
+    pub fn add_authority(mut self, record: Record<'a>) -> Self {
+        self.authorities.push(record);
+        self
+    }
+
+    pub fn add_additional(mut self, record: Record<'a>) -> Self {
+        self.additionals.push(record);
+        self
+    }
+
+    pub fn build(self) -> Result<'static, Vec<u8>> {
+        Ok(self.build_with_omissions()?.0)
+    }
+
+    /// Build the message and return it along with the records which
+    /// did not fit into the maximum size. Following RFC 2181, section
+    /// 9, whole RRsets are dropped from the end of the message and the
+    /// TC bit is set if answer or authority records were dropped, but
+    /// not if only additional records were. Questions and the OPT
+    /// record are always included.
+    pub fn build_with_omissions(self) -> Result<'static, (Vec<u8>, Omissions<'a>)> {
         let mut buffer = vec![0u8; 12];
 
         write_u16(&mut buffer, &mut 0, self.id);
 
         buffer[2] = (self.qr as u8) << 7;
-        buffer[2] |= (self.opcode as u8) << 3;
+        buffer[2] |= ((u16::from(self.opcode) & 0x0f) as u8) << 3;
         buffer[2] |= (self.aa as u8) << 2;
-        // TODO: set truncated message bit
         buffer[2] |= self.rd as u8;
 
         buffer[3] = (self.ra as u8) << 7;
-        buffer[3] |= self.z << 4;
-        buffer[3] |= (self.rcode as u8) & 0x0f;
+        buffer[3] |= (self.z as u8) << 6;
+        buffer[3] |= (self.ad as u8) << 5;
+        buffer[3] |= (self.cd as u8) << 4;
+        buffer[3] |= (u16::from(self.rcode) & 0x0f) as u8;
 
         write_u16(&mut buffer, &mut 4, self.questions.len() as u16);
 
-        let mut pos = 12;
         let mut encoded_labels = HashMap::new();
         for question in self.questions {
-            let encoded_labels = encode_labels(&mut encoded_labels, pos, question.address);
+            let pos = buffer.len();
+            let encoded_labels = encode_labels(&mut encoded_labels, pos, &question.name);
             buffer.extend(&encoded_labels);
-            pos += encoded_labels.len();
 
-            append_u16(&mut buffer, question.qtype as u16);
+            append_u16(&mut buffer, u16::from(question.qtype));
+
+            append_u16(&mut buffer, u16::from(question.qclass));
+        }
+
+        let rcode = u16::from(self.rcode) & 0x0fff;
+        let edns = self.edns.map(|mut edns| {
+            edns.extended_rcode = (rcode >> 4) as u8;
+            edns
+        });
+
+        let max_size = match (self.max_size, &edns) {
+            (Some(max_size), _) => max_size,
+            (None, Some(edns)) => cmp::max(edns.udp_payload_size as usize, MAX_UDP_SIZE),
+            (None, None) => MAX_UDP_SIZE,
+        };
+        // leave room for the OPT record
+        let max_size = max_size.saturating_sub(edns.as_ref().map_or(0, Edns::wire_len));
+
+        let mut truncated = false;
+        let (ancount, answers) = encode_section(
+            &mut buffer, &mut encoded_labels, max_size, &mut truncated, self.answers)?;
+        let (nscount, authorities) = encode_section(
+            &mut buffer, &mut encoded_labels, max_size, &mut truncated, self.authorities)?;
+        let (mut arcount, additionals) = encode_section(
+            &mut buffer, &mut encoded_labels, max_size, &mut truncated, self.additionals)?;
+
+        if let Some(ref edns) = edns {
+            edns.encode(&mut buffer);
+            arcount += 1;
+        }
 
-            append_u16(&mut buffer, question.qclass as u16);
+        if !answers.is_empty() || !authorities.is_empty() {
+            buffer[2] |= 0b_0000_0010;
         }
 
-        buffer
+        let mut pos = 6;
+        write_u16(&mut buffer, &mut pos, ancount);
+        write_u16(&mut buffer, &mut pos, nscount);
+        write_u16(&mut buffer, &mut pos, arcount);
+
+        Ok((buffer, Omissions { answers, authorities, additionals }))
     }
 }
 
+impl<'a> Default for DnsMessageBuilder<'a> {
+    fn default() -> Self {
+        DnsMessageBuilder::new()
+    }
+}
+
+/// Append the RRsets of a section to `buffer` as long as they fit into
+/// `max_size`. Once an RRset doesn't fit, it and all following ones
+/// are returned as omitted, together with the number of records
+/// written. Fails if a record can't be encoded at all.
+fn encode_section<'a>(
+    buffer: &mut Vec<u8>,
+    encoded_labels: &mut HashMap<Name<'a>, usize>,
+    max_size: usize,
+    truncated: &mut bool,
+    records: Vec<Record<'a>>
+) -> Result<'static, (u16, Vec<Record<'a>>)> {
+    let mut count = 0;
+    let mut omitted = vec![];
+
+    for rrset in rrsets(records) {
+        if !*truncated {
+            let len = buffer.len();
+            let labels = encoded_labels.clone();
+            for record in &rrset {
+                record.encode(buffer, encoded_labels)?;
+            }
+
+            if buffer.len() <= max_size {
+                count += rrset.len() as u16;
+                continue;
+            }
+
+            // forget the names written by the dropped records too
+            buffer.truncate(len);
+            *encoded_labels = labels;
+            *truncated = true;
+        }
+        omitted.extend(rrset);
+    }
+
+    Ok((count, omitted))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use ::parse;
     use super::question::Question;
 
-    type ParsedQuestion<'a> = ::Question<'a>;
+    use ::ParsedQuestion;
+    use ::dns_record::{RecordPayload, Soa, Mx};
+    use std::borrow::Cow;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn record(address: &str, payload: RecordPayload<'static>) -> Record<'static> {
+        Record::new(address.parse().unwrap(), Class::IN, 300, payload)
+    }
 
     #[test]
     fn should_init_with_default_id() {
@@ -133,7 +298,7 @@ mod test {
         let mut ids = HashSet::new();
 
         for _ in 0..num_tests {
-            let buffer = DnsMessageBuilder::new().build();
+            let buffer = DnsMessageBuilder::new().build().unwrap();
             let result = parse(&buffer).unwrap();
             ids.insert(result.id());
         }
@@ -145,14 +310,14 @@ mod test {
     fn should_allow_id_override() {
         let buffer = DnsMessageBuilder::new()
             .with_id(5)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(5, result.id());
     }
 
     #[test]
     fn should_default_to_query() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(QR::QUERY, result.qr());
     }
@@ -161,14 +326,14 @@ mod test {
     fn should_allow_qr_override() {
         let buffer = DnsMessageBuilder::new()
             .with_qr(QR::RESPONSE)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(QR::RESPONSE, result.qr());
     }
 
     #[test]
     fn should_default_to_query_opcode() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(OPCODE::QUERY, result.opcode());
     }
@@ -177,86 +342,137 @@ mod test {
     fn should_allow_opcode_override() {
         let buffer = DnsMessageBuilder::new()
             .with_opcode(OPCODE::IQUERY)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(QR::QUERY, result.qr());
         assert_eq!(OPCODE::IQUERY, result.opcode());
     }
 
+    #[test]
+    fn should_build_notify_and_update_messages() {
+        let buffer = DnsMessageBuilder::new()
+            .with_opcode(OPCODE::NOTIFY)
+            .with_aa(true)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::SOA, Qclass::IN))
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert_eq!(OPCODE::NOTIFY, result.opcode());
+        assert!(result.aa());
+
+        let buffer = DnsMessageBuilder::new()
+            .with_opcode(OPCODE::UPDATE)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::SOA, Qclass::IN))
+            .add_authority(record("www.example.com", RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1))))
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert_eq!(OPCODE::UPDATE, result.opcode());
+        assert_eq!(Qtype::SOA, result.zone()[0].qtype);
+        assert!(result.prerequisites().is_empty());
+        assert_eq!(1, result.updates().len());
+    }
+
     #[test]
     fn should_default_to_non_authoritative_answer() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(false, result.aa());
+        assert!(!result.aa());
     }
 
     #[test]
     fn should_allow_aa_override() {
         let buffer = DnsMessageBuilder::new()
             .with_aa(true)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert!(result.aa());
     }
 
     #[test]
     fn should_default_to_non_truncated_message() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(false, result.tc());
+        assert!(!result.tc());
     }
 
     #[test]
-    fn should_default_to_no_resultursion_desired() {
-        let buffer = DnsMessageBuilder::new().build();
+    fn should_default_to_recursion_desired() {
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(false, result.rd());
+        assert!(result.rd());
     }
 
     #[test]
     fn should_allow_setting_resultursion_desired() {
         let buffer = DnsMessageBuilder::new()
             .with_rd(true)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert!(result.rd());
     }
 
     #[test]
     fn should_default_to_no_resultursion_available() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(false, result.ra());
+        assert!(!result.ra());
     }
 
     #[test]
     fn should_allow_setting_resultursion_avaiable() {
         let buffer = DnsMessageBuilder::new()
             .with_ra(true)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert!(result.ra());
     }
 
     #[test]
-    fn should_default_to_zeroed_dnssec_bits() {
-        let buffer = DnsMessageBuilder::new().build();
+    fn should_default_to_cleared_z_ad_and_cd() {
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(0, result.dnssec() & 0b_0111_0000);
+        assert!(!result.z());
+        assert!(!result.ad());
+        assert!(!result.cd());
     }
 
     #[test]
-    fn should_allow_setting_dnssec_bits() {
+    fn should_allow_setting_z() {
         let buffer = DnsMessageBuilder::new()
-            .with_dnssec_bits(0xff)
-            .build();
+            .with_z(true)
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
-        assert_eq!(0b_0000_0111, result.dnssec());
+        assert!(result.z());
+        assert!(!result.ad());
+        assert!(!result.cd());
+    }
+
+    #[test]
+    fn should_allow_setting_ad() {
+        let buffer = DnsMessageBuilder::new()
+            .with_ad(true)
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert!(!result.z());
+        assert!(result.ad());
+        assert!(!result.cd());
+    }
+
+    #[test]
+    fn should_allow_setting_cd() {
+        let buffer = DnsMessageBuilder::new()
+            .with_ra(true)
+            .with_cd(true)
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert!(result.ra());
+        assert!(!result.z());
+        assert!(!result.ad());
+        assert!(result.cd());
     }
 
     #[test]
     fn should_default_to_response_code_ok() {
-        let buffer = DnsMessageBuilder::new().build();
+        let buffer = DnsMessageBuilder::new().build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(RCODE::Ok, result.rcode());
     }
@@ -265,7 +481,7 @@ mod test {
     fn should_allow_setting_the_response_code() {
         let buffer = DnsMessageBuilder::new()
             .with_rcode(RCODE::NotImplemented)
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(RCODE::NotImplemented, result.rcode());
     }
@@ -274,45 +490,61 @@ mod test {
     fn should_allow_adding_questions() {
         let buffer = DnsMessageBuilder::new()
             .add_question(Question::new(
-                "www.aaa",
+                "www.aaa".parse().unwrap(),
                 Qtype::MD,
                 Qclass::Wildcard
             ))
             .add_question(Question::new(
-                "heise.de",
+                "heise.de".parse().unwrap(),
                 Qtype::A,
                 Qclass::IN
             ))
-            .build();
+            .build().unwrap();
         let result = parse(&buffer).unwrap();
         assert_eq!(
             vec![
-                ParsedQuestion::new(vec!["www", "aaa"], Qtype::MD, Qclass::Wildcard),
-                ParsedQuestion::new(vec!["heise", "de"], Qtype::A, Qclass::IN)
+                ParsedQuestion::new("www.aaa".parse().unwrap(), Qtype::MD, Qclass::Wildcard),
+                ParsedQuestion::new("heise.de".parse().unwrap(), Qtype::A, Qclass::IN)
             ],
             result.questions()
         );
     }
 
+    #[test]
+    fn should_allow_adding_aaaa_questions() {
+        let buffer = DnsMessageBuilder::new()
+            .add_question(Question::new(
+                "google.com".parse().unwrap(),
+                Qtype::AAAA,
+                Qclass::IN
+            ))
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![ParsedQuestion::new("google.com".parse().unwrap(), Qtype::AAAA, Qclass::IN)],
+            result.questions()
+        );
+    }
+
     #[test]
     fn should_build_with_label_refs() {
         let buffer = DnsMessageBuilder::new()
             .add_question(Question::new(
-                "www.aaa",
+                "www.aaa".parse().unwrap(),
                 Qtype::MD,
                 Qclass::Wildcard
             ))
             .add_question(Question::new(
-                "www.aaa",
+                "www.aaa".parse().unwrap(),
                 Qtype::MD,
                 Qclass::Wildcard
             ))
             .add_question(Question::new(
-                "xxx.aaa.bbb",
+                "xxx.aaa.bbb".parse().unwrap(),
                 Qtype::MD,
                 Qclass::Wildcard
             ))
-            .build();
+            .build().unwrap();
 
         println!("{:?}", &buffer[12..]);
 
@@ -324,4 +556,279 @@ mod test {
         assert_eq!(&buffer[35..39], &[3, 97, 97, 97]);
         assert_eq!(&buffer[39..44], &[3, 98, 98, 98, 0]);
     }
+
+    #[test]
+    fn should_build_with_suffix_label_refs() {
+        let buffer = DnsMessageBuilder::new()
+            .add_question(Question::new(
+                "example.com".parse().unwrap(),
+                Qtype::A,
+                Qclass::IN
+            ))
+            .add_question(Question::new(
+                "mail.example.com".parse().unwrap(),
+                Qtype::MX,
+                Qclass::IN
+            ))
+            .add_question(Question::new(
+                "www.mail.example.com".parse().unwrap(),
+                Qtype::AAAA,
+                Qclass::IN
+            ))
+            .build().unwrap();
+
+        assert_eq!(&buffer[29..36], &[4, b'm', b'a', b'i', b'l', 0xc0, 12]);
+        assert_eq!(&buffer[40..46], &[3, b'w', b'w', b'w', 0xc0, 29]);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![
+                ParsedQuestion::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN),
+                ParsedQuestion::new("mail.example.com".parse().unwrap(), Qtype::MX, Qclass::IN),
+                ParsedQuestion::new("www.mail.example.com".parse().unwrap(), Qtype::AAAA, Qclass::IN)
+            ],
+            result.questions()
+        );
+    }
+
+    #[test]
+    fn should_build_label_refs_beyond_255_bytes() {
+        let names: Vec<String> = (0..40)
+            .map(|i| format!("host{:02}.example.com", i))
+            .collect();
+        let builder = names.iter()
+            .fold(DnsMessageBuilder::new(), |builder, name| builder.add_question(Question::new(
+                name.parse().unwrap(),
+                Qtype::A,
+                Qclass::IN
+            )));
+        let buffer = builder.build().unwrap();
+        assert!(buffer.len() > 255);
+
+        let result = parse(&buffer).unwrap();
+        let questions: Vec<String> = result.questions().iter()
+            .map(|q| q.name.to_string())
+            .collect();
+        let expected: Vec<String> = names.iter()
+            .map(|name| format!("{}.", name))
+            .collect();
+        assert_eq!(expected, questions);
+    }
+
+    #[test]
+    fn should_build_all_sections() {
+        let answers = [
+            record("example.com", RecordPayload::MX(Mx { preference: 10, exchange: "mail.example.com".parse().unwrap() })),
+            record("example.com", RecordPayload::TXT(vec![Cow::from(&b"v=spf1 mx -all"[..])])),
+        ];
+        let authorities = [
+            record("example.com", RecordPayload::SOA(Soa {
+                mname: "ns.example.com".parse().unwrap(),
+                rname: "hostmaster.example.com".parse().unwrap(),
+                serial: 1,
+                refresh: 3600,
+                retry: 600,
+                expire: 86400,
+                minimum: 300
+            })),
+            record("example.com", RecordPayload::NS("ns.example.com".parse().unwrap())),
+        ];
+        let additionals = [
+            record("mail.example.com", RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1))),
+            record("mail.example.com", RecordPayload::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1))),
+        ];
+
+        let builder = DnsMessageBuilder::new()
+            .with_qr(QR::RESPONSE)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::MX, Qclass::IN));
+        let builder = answers.iter().cloned().fold(builder, DnsMessageBuilder::add_answer);
+        let builder = authorities.iter().cloned().fold(builder, DnsMessageBuilder::add_authority);
+        let builder = additionals.iter().cloned().fold(builder, DnsMessageBuilder::add_additional);
+        let buffer = builder.build().unwrap();
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(&answers[..], result.answers());
+        assert_eq!(&authorities[..], result.authorities());
+        assert_eq!(&additionals[..], result.additionals());
+    }
+
+    #[test]
+    fn should_compress_names_in_records() {
+        let buffer = DnsMessageBuilder::new()
+            .with_qr(QR::RESPONSE)
+            .add_question(Question::new("www.example.com".parse().unwrap(), Qtype::CNAME, Qclass::IN))
+            .add_answer(record("www.example.com", RecordPayload::CNAME("web.example.com".parse().unwrap())))
+            .build().unwrap();
+
+        // question name at 12, its "example.com" suffix at 16
+        let answer = 12 + 17 + 4;
+        assert_eq!(&buffer[answer..answer + 2], &[0xc0, 12]);
+        let rdata = answer + 2 + 10;
+        assert_eq!(&buffer[rdata - 2..], &[0, 6, 3, b'w', b'e', b'b', 0xc0, 16]);
+    }
+
+    fn a_records(address: &str, count: u8) -> Vec<Record<'static>> {
+        (0..count)
+            .map(|i| record(address, RecordPayload::A(Ipv4Addr::new(192, 0, 2, i))))
+            .collect()
+    }
+
+    fn build_with(answers: Vec<Record<'static>>, additionals: Vec<Record<'static>>, max_size: usize) -> (Vec<u8>, Omissions<'static>) {
+        let builder = DnsMessageBuilder::new()
+            .with_qr(QR::RESPONSE)
+            .with_max_size(max_size)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN));
+        let builder = answers.into_iter().fold(builder, DnsMessageBuilder::add_answer);
+        let builder = additionals.into_iter().fold(builder, DnsMessageBuilder::add_additional);
+        builder.build_with_omissions().unwrap()
+    }
+
+    #[test]
+    fn should_default_to_512_bytes() {
+        // 12 bytes header, 27 bytes for the first record and 16 for
+        // each following one, which refers to the first one's name
+        let buffer = a_records("example.com", 30).into_iter()
+            .fold(DnsMessageBuilder::new(), DnsMessageBuilder::add_answer)
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert!(!result.tc());
+        assert_eq!(30, result.answers().len());
+
+        let buffer = a_records("example.com", 31).into_iter()
+            .fold(DnsMessageBuilder::new(), DnsMessageBuilder::add_answer)
+            .build().unwrap();
+        let result = parse(&buffer).unwrap();
+        assert!(result.tc());
+        assert!(result.answers().is_empty());
+    }
+
+    #[test]
+    fn should_not_refer_to_dropped_names() {
+        let mut buffer = vec![0u8; 12];
+        let mut encoded_labels = HashMap::new();
+        encode_labels(&mut encoded_labels, 12, &"example.com".parse().unwrap());
+        buffer.extend(vec![0; 13]);
+
+        let (count, omitted) = encode_section(
+            &mut buffer, &mut encoded_labels, 40, &mut false, a_records("www.example.com", 1)).unwrap();
+        assert_eq!(0, count);
+        assert_eq!(1, omitted.len());
+        assert_eq!(25, buffer.len());
+
+        let encoded = encode_labels(&mut encoded_labels, buffer.len(), &"mail.www.example.com".parse().unwrap());
+        assert_eq!(&encoded, &[4, b'm', b'a', b'i', b'l', 3, b'w', b'w', b'w', 0xc0, 12]);
+    }
+
+    #[test]
+    fn should_drop_whole_rrsets_and_set_tc() {
+        let mut answers = a_records("example.com", 2);
+        answers.extend(a_records("www.example.com", 3));
+        let (buffer, omissions) = build_with(answers, vec![], 29 + 2 * 16 + 20);
+
+        let result = parse(&buffer).unwrap();
+        assert!(result.tc());
+        assert_eq!(&a_records("example.com", 2)[..], result.answers());
+        assert_eq!(a_records("www.example.com", 3), omissions.answers);
+    }
+
+    #[test]
+    fn should_not_set_tc_when_dropping_additionals() {
+        let (buffer, omissions) = build_with(
+            a_records("example.com", 1),
+            a_records("ns.example.com", 2),
+            29 + 16 + 20
+        );
+
+        let result = parse(&buffer).unwrap();
+        assert!(!result.tc());
+        assert_eq!(1, result.answers().len());
+        assert!(result.additionals().is_empty());
+        assert_eq!(a_records("ns.example.com", 2), omissions.additionals);
+    }
+
+    #[test]
+    fn should_drop_all_rrsets_after_the_first_omitted_one() {
+        let mut answers = a_records("www.example.com", 3);
+        answers.extend(a_records("example.com", 1));
+        let (buffer, omissions) = build_with(answers, a_records("example.com", 1), 29 + 40);
+
+        let result = parse(&buffer).unwrap();
+        assert!(result.tc());
+        assert!(result.answers().is_empty());
+        assert_eq!(4, omissions.answers.len());
+        assert_eq!(1, omissions.additionals.len());
+    }
+
+    #[test]
+    fn should_allow_larger_messages() {
+        let (buffer, omissions) = build_with(a_records("example.com", 100), vec![], 4096);
+        assert!(omissions.is_empty());
+        assert_eq!(100, parse(&buffer).unwrap().answers().len());
+    }
+
+    #[test]
+    fn should_build_edns() {
+        let mut edns = Edns::new(4096);
+        edns.dnssec_ok = true;
+        let buffer = DnsMessageBuilder::new()
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN))
+            .add_additional(record("example.com", RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1))))
+            .with_edns(edns.clone())
+            .build().unwrap();
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(1, result.additionals().len());
+        assert_eq!(Some(&edns), result.edns());
+    }
+
+    #[test]
+    fn should_default_to_edns_payload_size() {
+        let answers = a_records("example.com", 100);
+        let builder = answers.into_iter()
+            .fold(DnsMessageBuilder::new(), DnsMessageBuilder::add_answer)
+            .with_edns(Edns::new(4096));
+        let (buffer, omissions) = builder.build_with_omissions().unwrap();
+        assert!(omissions.is_empty());
+        assert_eq!(100, parse(&buffer).unwrap().answers().len());
+
+        // payload sizes below 512 are treated as 512
+        let builder = a_records("example.com", 20).into_iter()
+            .fold(DnsMessageBuilder::new(), DnsMessageBuilder::add_answer)
+            .with_edns(Edns::new(100));
+        let (buffer, omissions) = builder.build_with_omissions().unwrap();
+        assert!(omissions.is_empty());
+        assert!(buffer.len() > 100);
+    }
+
+    #[test]
+    fn should_keep_room_for_the_opt_record() {
+        // 29 bytes header and question, 16 per record, 11 for OPT
+        let builder = DnsMessageBuilder::new()
+            .with_max_size(29 + 2 * 16 + 11 - 1)
+            .with_edns(Edns::new(4096))
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN));
+        let builder = a_records("example.com", 1).into_iter()
+            .chain(a_records("www.example.com", 1))
+            .fold(builder, DnsMessageBuilder::add_answer);
+        let (buffer, omissions) = builder.build_with_omissions().unwrap();
+
+        let result = parse(&buffer).unwrap();
+        assert!(result.tc());
+        assert_eq!(1, result.answers().len());
+        assert_eq!(1, omissions.answers.len());
+        assert!(result.edns().is_some());
+    }
+
+    #[test]
+    fn should_build_extended_rcodes() {
+        let buffer = DnsMessageBuilder::new()
+            .with_rcode(RCODE::BadCookie)
+            .with_edns(Edns::new(4096))
+            .build().unwrap();
+
+        assert_eq!(7, buffer[3] & 0x0f);
+        let result = parse(&buffer).unwrap();
+        assert_eq!(RCODE::BadCookie, result.rcode());
+        assert_eq!(1, result.edns().unwrap().extended_rcode);
+    }
 }
@@ -1,18 +1,19 @@
 use ::enums::{Qclass, Qtype};
+use ::name::Name;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Question<'a> {
-    pub address: &'a str,
+    pub name: Name<'a>,
     pub qtype: Qtype,
     pub qclass: Qclass
 }
 
 impl<'a> Question<'a> {
     pub fn new(
-        address: &'a str,
+        name: Name<'a>,
         qtype: Qtype,
         qclass: Qclass
     ) -> Self {
-        Question { address, qtype, qclass }
+        Question { name, qtype, qclass }
     }
 }
@@ -0,0 +1,69 @@
+use ::dns_record::Record;
+
+/// The records `DnsMessageBuilder::build_with_omissions` left out of a
+/// message to stay within its maximum size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Omissions<'a> {
+    pub answers: Vec<Record<'a>>,
+    pub authorities: Vec<Record<'a>>,
+    pub additionals: Vec<Record<'a>>,
+}
+
+impl<'a> Omissions<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.answers.is_empty() && self.authorities.is_empty() && self.additionals.is_empty()
+    }
+}
+
+/// Group the records of a section into RRsets, i.e. records sharing
+/// owner name, class and type, in the order of their first occurrence.
+pub fn rrsets<'a>(records: Vec<Record<'a>>) -> Vec<Vec<Record<'a>>> {
+    let mut rrsets: Vec<Vec<Record<'a>>> = vec![];
+
+    for record in records {
+        let pos = rrsets.iter().position(|rrset| {
+            let first = &rrset[0];
+            first.name == record.name
+                && first.class == record.class
+                && first.payload.typ() == record.payload.typ()
+        });
+        match pos {
+            Some(pos) => rrsets[pos].push(record),
+            None => rrsets.push(vec![record]),
+        }
+    }
+
+    rrsets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use ::enums::Class;
+    use ::dns_record::RecordPayload;
+
+    fn a(address: &str, last: u8) -> Record<'static> {
+        Record::new(address.parse().unwrap(), Class::IN, 300, RecordPayload::A(Ipv4Addr::new(192, 0, 2, last)))
+    }
+
+    #[test]
+    fn should_group_rrsets_by_first_occurrence() {
+        let cname = Record::new(
+            "www.example.com".parse().unwrap(),
+            Class::IN,
+            300,
+            RecordPayload::CNAME("example.com".parse().unwrap())
+        );
+        let records = vec![a("example.com", 1), cname.clone(), a("EXAMPLE.com", 2), a("www.example.com", 3)];
+
+        assert_eq!(
+            vec![
+                vec![a("example.com", 1), a("EXAMPLE.com", 2)],
+                vec![cname],
+                vec![a("www.example.com", 3)],
+            ],
+            rrsets(records)
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::net::IpAddr;
+use rand::{Rng, thread_rng};
+use siphasher::sip::SipHasher24;
+use ::dns_record::DnsRecord;
+use ::edns::Cookie;
+
+/// Server cookies are accepted up to an hour after they were created
+/// and up to five minutes before, to allow for clock skew (RFC 9018).
+const MAX_COOKIE_AGE: i64 = 3600;
+const MAX_COOKIE_SKEW: i64 = 300;
+
+const SERVER_COOKIE_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieStatus {
+    /// The message carries no COOKIE option.
+    Missing,
+    /// The cookie is valid, but there is no server cookie.
+    ClientOnly,
+    Valid,
+    /// The client cookie of a response doesn't match the one sent, or
+    /// the server cookie of a request wasn't handed out by us.
+    Invalid,
+}
+
+/// Client side cookies: creates a client cookie per server and
+/// remembers the server cookies received in return.
+///
+/// The client cookies are derived from a random secret. A client
+/// should use a new jar whenever its own address changes, so it can't
+/// be tracked across networks.
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    secret: [u8; 16],
+    server_cookies: HashMap<IpAddr, Vec<u8>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar::with_secret(thread_rng().gen())
+    }
+
+    pub fn with_secret(secret: [u8; 16]) -> Self {
+        CookieJar { secret, server_cookies: HashMap::new() }
+    }
+
+    pub fn client_cookie(&self, server: IpAddr) -> [u8; 8] {
+        let mut hasher = sip_hasher(&self.secret);
+        write_address(&mut hasher, server);
+        hasher.finish().to_le_bytes()
+    }
+
+    /// The cookie to send to `server`, including its server cookie if
+    /// we know it.
+    pub fn cookie(&self, server: IpAddr) -> Cookie<'static> {
+        let client = self.client_cookie(server);
+        match self.server_cookies.get(&server) {
+            // server cookies are checked before being stored
+            Some(server_cookie) => Cookie::with_server(client, server_cookie.clone()).unwrap(),
+            None => Cookie::new(client),
+        }
+    }
+
+    /// Check the cookie of a response from `server` and remember its
+    /// server cookie. Responses with an `Invalid` cookie should be
+    /// discarded as spoofed (RFC 7873, section 5.3).
+    pub fn update(&mut self, server: IpAddr, response: &DnsRecord) -> CookieStatus {
+        let cookie = match response.edns().and_then(|edns| edns.cookie()) {
+            Some(cookie) => cookie,
+            None => return CookieStatus::Missing,
+        };
+
+        if !constant_time_eq(cookie.client(), &self.client_cookie(server)) {
+            return CookieStatus::Invalid;
+        }
+        match cookie.server() {
+            Some(server_cookie) => {
+                self.server_cookies.insert(server, server_cookie.to_vec());
+                CookieStatus::Valid
+            },
+            None => CookieStatus::ClientOnly,
+        }
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        CookieJar::new()
+    }
+}
+
+/// Server side cookies as specified in RFC 9018: a version, a
+/// timestamp and a SipHash-2-4 over the client cookie, the timestamp
+/// and the client's address, keyed with a server secret.
+///
+/// Secrets should be rotated regularly. Cookies created with the
+/// previous secret remain valid until the next rotation.
+#[derive(Debug, Clone)]
+pub struct ServerCookies {
+    secret: [u8; 16],
+    previous: Option<[u8; 16]>,
+}
+
+impl ServerCookies {
+    pub fn new(secret: [u8; 16]) -> Self {
+        ServerCookies { secret, previous: None }
+    }
+
+    pub fn rotate(&mut self, secret: [u8; 16]) {
+        self.previous = Some(self.secret);
+        self.secret = secret;
+    }
+
+    /// Create the server cookie for `client`. `now` is the current
+    /// time in seconds since the epoch.
+    pub fn generate(&self, client_cookie: &[u8; 8], client: IpAddr, now: u32) -> Vec<u8> {
+        let mut cookie = vec![SERVER_COOKIE_VERSION, 0, 0, 0];
+        cookie.extend(&now.to_be_bytes());
+        let hash = server_hash(&self.secret, client_cookie, &cookie, client);
+        cookie.extend(&hash);
+        cookie
+    }
+
+    /// The cookie to answer a request with `request_cookie` with.
+    pub fn response_cookie(&self, request_cookie: &Cookie, client: IpAddr, now: u32) -> Cookie<'static> {
+        let server = self.generate(request_cookie.client(), client, now);
+        Cookie::with_server(*request_cookie.client(), server).unwrap()
+    }
+
+    /// Check the cookie of a request from `client`. Requests with an
+    /// `Invalid` cookie, and optionally those without server cookie,
+    /// should be answered with `RCODE::BadCookie` and a fresh cookie.
+    pub fn verify(&self, request: &DnsRecord, client: IpAddr, now: u32) -> CookieStatus {
+        let cookie = match request.edns().and_then(|edns| edns.cookie()) {
+            Some(cookie) => cookie,
+            None => return CookieStatus::Missing,
+        };
+        let server = match cookie.server() {
+            Some(server) => server,
+            None => return CookieStatus::ClientOnly,
+        };
+
+        if server.len() != 16 || server[0] != SERVER_COOKIE_VERSION {
+            return CookieStatus::Invalid;
+        }
+
+        let timestamp = u32::from_be_bytes([server[4], server[5], server[6], server[7]]);
+        // serial number arithmetic, the timestamp wraps in 2106
+        let age = now.wrapping_sub(timestamp) as i32 as i64;
+        if !(-MAX_COOKIE_SKEW..=MAX_COOKIE_AGE).contains(&age) {
+            return CookieStatus::Invalid;
+        }
+
+        let secrets = Some(self.secret).into_iter().chain(self.previous);
+        for secret in secrets {
+            if constant_time_eq(&server_hash(&secret, cookie.client(), &server[..8], client), &server[8..]) {
+                return CookieStatus::Valid;
+            }
+        }
+        CookieStatus::Invalid
+    }
+}
+
+fn server_hash(secret: &[u8; 16], client_cookie: &[u8; 8], header: &[u8], client: IpAddr) -> [u8; 8] {
+    let mut hasher = sip_hasher(secret);
+    hasher.write(client_cookie);
+    hasher.write(header);
+    write_address(&mut hasher, client);
+    hasher.finish().to_le_bytes()
+}
+
+/// Compare cookies without bailing out at the first differing byte,
+/// so the timing doesn't reveal how much of a forged cookie was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn sip_hasher(secret: &[u8; 16]) -> SipHasher24 {
+    let mut k0 = [0u8; 8];
+    let mut k1 = [0u8; 8];
+    k0.copy_from_slice(&secret[..8]);
+    k1.copy_from_slice(&secret[8..]);
+    SipHasher24::new_with_keys(u64::from_le_bytes(k0), u64::from_le_bytes(k1))
+}
+
+fn write_address(hasher: &mut SipHasher24, address: IpAddr) {
+    match address {
+        IpAddr::V4(address) => hasher.write(&address.octets()),
+        IpAddr::V6(address) => hasher.write(&address.octets()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::builder::{DnsMessageBuilder, Question};
+    use ::edns::{Edns, EdnsOption};
+    use ::enums::{Qtype, Qclass, RCODE};
+    use ::parse;
+
+    const NOW: u32 = 1_500_000_000;
+
+    fn client() -> IpAddr {
+        "192.0.2.53".parse().unwrap()
+    }
+
+    fn server() -> IpAddr {
+        "198.51.100.1".parse().unwrap()
+    }
+
+    fn message(cookie: Option<Cookie>, rcode: RCODE) -> Vec<u8> {
+        let mut edns = Edns::new(1232);
+        edns.options.extend(cookie.map(EdnsOption::Cookie));
+        DnsMessageBuilder::new()
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN))
+            .with_rcode(rcode)
+            .with_edns(edns)
+            .build().unwrap()
+    }
+
+    #[test]
+    fn should_create_client_cookies_per_server() {
+        let jar = CookieJar::with_secret([1; 16]);
+        assert_eq!(jar.client_cookie(server()), jar.client_cookie(server()));
+        assert!(jar.client_cookie(server()) != jar.client_cookie(client()));
+        assert!(jar.client_cookie(server()) != CookieJar::with_secret([2; 16]).client_cookie(server()));
+        assert_eq!(None, jar.cookie(server()).server());
+        assert!(CookieJar::default().client_cookie(server()) != CookieJar::default().client_cookie(server()));
+    }
+
+    #[test]
+    fn should_compare_cookies_bytewise() {
+        assert!(constant_time_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2]));
+        assert!(constant_time_eq(&[], &[]));
+    }
+
+    #[test]
+    fn should_remember_server_cookies() {
+        let mut jar = CookieJar::new();
+
+        let response = message(None, RCODE::Ok);
+        assert_eq!(CookieStatus::Missing, jar.update(server(), &parse(&response).unwrap()));
+
+        let cookie = Cookie::with_server(jar.client_cookie(server()), vec![7; 16]).unwrap();
+        let response = message(Some(cookie), RCODE::Ok);
+        assert_eq!(CookieStatus::Valid, jar.update(server(), &parse(&response).unwrap()));
+        assert_eq!(Some(&[7; 16][..]), jar.cookie(server()).server());
+        assert_eq!(None, jar.cookie(client()).server());
+    }
+
+    #[test]
+    fn should_reject_responses_with_foreign_client_cookies() {
+        let mut jar = CookieJar::new();
+        let cookie = Cookie::with_server([0; 8], vec![7; 16]).unwrap();
+        let response = message(Some(cookie), RCODE::Ok);
+
+        assert_eq!(CookieStatus::Invalid, jar.update(server(), &parse(&response).unwrap()));
+        assert_eq!(None, jar.cookie(server()).server());
+    }
+
+    #[test]
+    fn should_create_server_cookies_as_in_rfc_9018() {
+        // RFC 9018, appendix A.1
+        let cookies = ServerCookies::new([
+            0xe5, 0xe9, 0x73, 0xe5, 0xa6, 0xb2, 0xa4, 0x3f,
+            0x48, 0xe7, 0xdc, 0x84, 0x9e, 0x37, 0xbf, 0xcf
+        ]);
+        let client_cookie = [0x24, 0x64, 0xc4, 0xab, 0xcf, 0x10, 0xc9, 0x57];
+        let cookie = cookies.generate(&client_cookie, "198.51.100.100".parse().unwrap(), 1559731985);
+
+        assert_eq!(
+            vec![
+                0x01, 0x00, 0x00, 0x00, 0x5c, 0xf7, 0x9f, 0x11,
+                0x1f, 0x81, 0x30, 0xc3, 0xee, 0xe2, 0x94, 0x80
+            ],
+            cookie
+        );
+    }
+
+    #[test]
+    fn should_verify_server_cookies() {
+        let cookies = ServerCookies::new([3; 16]);
+        let verify = |cookie: Option<Cookie>, client: IpAddr, now: u32| {
+            cookies.verify(&parse(&message(cookie, RCODE::Ok)).unwrap(), client, now)
+        };
+        let valid = cookies.response_cookie(&Cookie::new([5; 8]), client(), NOW);
+
+        assert_eq!(CookieStatus::Missing, verify(None, client(), NOW));
+        assert_eq!(CookieStatus::ClientOnly, verify(Some(Cookie::new([5; 8])), client(), NOW));
+        assert_eq!(CookieStatus::Valid, verify(Some(valid.clone()), client(), NOW));
+        assert_eq!(CookieStatus::Valid, verify(Some(valid.clone()), client(), NOW + 3600));
+        assert_eq!(CookieStatus::Valid, verify(Some(valid.clone()), client(), NOW - 300));
+
+        assert_eq!(CookieStatus::Invalid, verify(Some(valid.clone()), client(), NOW + 3601));
+        assert_eq!(CookieStatus::Invalid, verify(Some(valid.clone()), client(), NOW - 301));
+        assert_eq!(CookieStatus::Invalid, verify(Some(valid.clone()), server(), NOW));
+
+        let foreign_client = Cookie::with_server([6; 8], valid.server().unwrap().to_vec()).unwrap();
+        assert_eq!(CookieStatus::Invalid, verify(Some(foreign_client), client(), NOW));
+        let mut tampered = valid.server().unwrap().to_vec();
+        tampered[15] ^= 1;
+        assert_eq!(CookieStatus::Invalid, verify(Some(Cookie::with_server([5; 8], tampered).unwrap()), client(), NOW));
+        assert_eq!(CookieStatus::Invalid, verify(Some(Cookie::with_server([5; 8], vec![1; 8]).unwrap()), client(), NOW));
+    }
+
+    #[test]
+    fn should_accept_cookies_of_the_previous_secret() {
+        let mut cookies = ServerCookies::new([3; 16]);
+        let cookie = cookies.response_cookie(&Cookie::new([5; 8]), client(), NOW);
+        let request = message(Some(cookie), RCODE::Ok);
+        let request = parse(&request).unwrap();
+
+        cookies.rotate([4; 16]);
+        assert_eq!(CookieStatus::Valid, cookies.verify(&request, client(), NOW));
+        cookies.rotate([5; 16]);
+        assert_eq!(CookieStatus::Invalid, cookies.verify(&request, client(), NOW));
+    }
+
+    #[test]
+    fn should_establish_cookies_through_badcookie() {
+        let mut jar = CookieJar::new();
+        let cookies = ServerCookies::new([3; 16]);
+
+        // the first request only carries a client cookie
+        let request = message(Some(jar.cookie(server())), RCODE::Ok);
+        let request = parse(&request).unwrap();
+        assert_eq!(CookieStatus::ClientOnly, cookies.verify(&request, client(), NOW));
+
+        let request_cookie = request.edns().unwrap().cookie().unwrap();
+        let response = message(Some(cookies.response_cookie(request_cookie, client(), NOW)), RCODE::BadCookie);
+        let response = parse(&response).unwrap();
+        assert_eq!(RCODE::BadCookie, response.rcode());
+        assert_eq!(CookieStatus::Valid, jar.update(server(), &response));
+
+        let request = message(Some(jar.cookie(server())), RCODE::Ok);
+        assert_eq!(CookieStatus::Valid, cookies.verify(&parse(&request).unwrap(), client(), NOW + 10));
+    }
+}
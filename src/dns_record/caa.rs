@@ -0,0 +1,198 @@
+use std::str;
+use ::dns_record::records::Caa;
+use ::name::Name;
+
+/// The properties a CA is expected to understand. A critical property
+/// with any other tag forbids issuance.
+const KNOWN_TAGS: [&str; 3] = ["issue", "issuewild", "iodef"];
+
+/// The value of an `issue` or `issuewild` property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaaIssuer<'r> {
+    /// The domain of the authorized CA, or `None` if no CA is authorized.
+    pub domain: Option<&'r str>,
+    pub parameters: Vec<(&'r str, &'r str)>
+}
+
+impl<'a> Caa<'a> {
+    /// Whether the Issuer Critical flag is set, i.e. CAs that don't
+    /// understand the property must not issue.
+    pub fn is_critical(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    /// The issuer of an `issue` or `issuewild` property. `None` for
+    /// other properties and for values not following the grammar of
+    /// RFC 8659, which authorize no CA.
+    pub fn issuer(&self) -> Option<CaaIssuer<'_>> {
+        if !self.tag.eq_ignore_ascii_case("issue") && !self.tag.eq_ignore_ascii_case("issuewild") {
+            return None;
+        }
+
+        let value = str::from_utf8(&self.value).ok()?;
+        let mut parts = value.splitn(2, ';');
+        let domain = trim(parts.next().unwrap());
+        if !domain.is_empty() && !domain.split('.').all(is_label) {
+            return None;
+        }
+
+        let mut parameters = vec![];
+        match parts.next().map(trim) {
+            None | Some("") => {},
+            Some(list) => {
+                for parameter in list.split(';') {
+                    let mut parts = parameter.splitn(2, '=');
+                    let tag = trim(parts.next().unwrap());
+                    let value = trim(parts.next()?);
+                    if !is_label(tag) || !value.bytes().all(|b| (0x21..=0x7e).contains(&b) && b != b';') {
+                        return None;
+                    }
+                    parameters.push((tag, value));
+                }
+            }
+        }
+
+        Some(CaaIssuer {
+            domain: if domain.is_empty() { None } else { Some(domain) },
+            parameters
+        })
+    }
+}
+
+/// Whether the CA with the domain `issuer` may issue a certificate for
+/// `domain` (RFC 8659). `rrsets` are the CAA RRsets found while
+/// climbing from `domain`, without its wildcard label, towards the
+/// root; only the first non-empty one is relevant. Without any, every
+/// CA may issue.
+pub fn caa_allows_issuance(domain: &Name, rrsets: &[Vec<&Caa>], issuer: &str) -> bool {
+    let rrset = match rrsets.iter().find(|rrset| !rrset.is_empty()) {
+        Some(rrset) => rrset,
+        None => return true,
+    };
+
+    let unknown = |caa: &&&Caa| !KNOWN_TAGS.iter().any(|tag| caa.tag.eq_ignore_ascii_case(tag));
+    if rrset.iter().filter(unknown).any(|caa| caa.is_critical()) {
+        return false;
+    }
+
+    // issuewild properties only apply to wildcard domains, and take
+    // precedence over issue properties there
+    let wildcard = domain.labels().next() == Some(&b"*"[..]);
+    let tag = if wildcard && rrset.iter().any(|caa| caa.tag.eq_ignore_ascii_case("issuewild")) {
+        "issuewild"
+    } else {
+        "issue"
+    };
+
+    let mut properties = rrset.iter().filter(|caa| caa.tag.eq_ignore_ascii_case(tag)).peekable();
+    if properties.peek().is_none() {
+        return true;
+    }
+
+    let issuer = issuer.trim_end_matches('.');
+    properties.any(|caa| {
+        caa.issuer()
+            .and_then(|authorized| authorized.domain)
+            .is_some_and(|authorized| authorized.eq_ignore_ascii_case(issuer))
+    })
+}
+
+fn trim(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+fn is_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::borrow::Cow;
+
+    fn caa(flags: u8, tag: &'static str, value: &'static str) -> Caa<'static> {
+        Caa { flags, tag: Cow::from(tag), value: Cow::from(value.as_bytes()) }
+    }
+
+    fn name(address: &str) -> Name<'static> {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn should_parse_issuers() {
+        let issuer = caa(0, "issue", " ca.example.net ; account=230123; policy = ev");
+        assert_eq!(
+            Some(CaaIssuer { domain: Some("ca.example.net"), parameters: vec![("account", "230123"), ("policy", "ev")] }),
+            issuer.issuer()
+        );
+        assert_eq!(Some(CaaIssuer { domain: None, parameters: vec![] }), caa(0, "issuewild", ";").issuer());
+        assert_eq!(None, caa(0, "iodef", "mailto:security@example.com").issuer());
+        assert_eq!(None, caa(0, "issue", "ca.example.net; account").issuer());
+        assert_eq!(None, caa(0, "issue", "ca.example.net; account=230123;").issuer());
+        assert_eq!(Some(CaaIssuer { domain: Some("ca.example.net"), parameters: vec![] }), caa(0, "issue", "ca.example.net;").issuer());
+        assert_eq!(None, caa(0, "issue", "-ca.example.net").issuer());
+        assert_eq!(None, caa(0, "issue", "%%%%%").issuer());
+    }
+
+    #[test]
+    fn should_allow_any_ca_without_caa_records() {
+        assert!(caa_allows_issuance(&name("www.example.com"), &[], "ca.example.net"));
+        assert!(caa_allows_issuance(&name("www.example.com"), &[vec![], vec![]], "ca.example.net"));
+    }
+
+    #[test]
+    fn should_use_the_closest_rrset() {
+        let www = caa(0, "issue", "ca.example.net");
+        let apex = caa(0, "issue", "ca.example.org");
+        let rrsets = vec![vec![], vec![&www], vec![&apex]];
+
+        assert!(caa_allows_issuance(&name("a.www.example.com"), &rrsets, "CA.example.net."));
+        assert!(!caa_allows_issuance(&name("a.www.example.com"), &rrsets, "ca.example.org"));
+    }
+
+    #[test]
+    fn should_deny_other_cas() {
+        let issue = caa(0, "issue", "ca.example.net; account=230123");
+        let nobody = caa(0, "issue", ";");
+        let malformed = caa(0, "issue", "ca.example.org; account");
+        let iodef = caa(0, "iodef", "mailto:security@example.com");
+
+        assert!(caa_allows_issuance(&name("example.com"), &[vec![&issue, &iodef]], "ca.example.net"));
+        assert!(!caa_allows_issuance(&name("example.com"), &[vec![&issue, &iodef]], "ca.example.org"));
+        assert!(!caa_allows_issuance(&name("example.com"), &[vec![&nobody]], "ca.example.net"));
+        assert!(!caa_allows_issuance(&name("example.com"), &[vec![&malformed]], "ca.example.org"));
+        assert!(caa_allows_issuance(&name("example.com"), &[vec![&iodef]], "ca.example.org"));
+    }
+
+    #[test]
+    fn should_prefer_issuewild_for_wildcards() {
+        let issue = caa(0, "issue", "ca.example.net");
+        let issuewild = caa(0, "ISSUEWILD", "ca.example.org");
+        let rrsets = vec![vec![&issue, &issuewild]];
+
+        assert!(caa_allows_issuance(&name("*.example.com"), &rrsets, "ca.example.org"));
+        assert!(!caa_allows_issuance(&name("*.example.com"), &rrsets, "ca.example.net"));
+        assert!(caa_allows_issuance(&name("example.com"), &rrsets, "ca.example.net"));
+        assert!(!caa_allows_issuance(&name("example.com"), &rrsets, "ca.example.org"));
+
+        assert!(caa_allows_issuance(&name("*.example.com"), &[vec![&issue]], "ca.example.net"));
+        assert!(caa_allows_issuance(&name("example.com"), &[vec![&issuewild]], "ca.example.net"));
+    }
+
+    #[test]
+    fn should_deny_on_unknown_critical_properties() {
+        let issue = caa(0, "issue", "ca.example.net");
+        let critical = caa(128, "tbs", "Unknown");
+        let noncritical = caa(0, "tbs", "Unknown");
+
+        assert!(critical.is_critical());
+        assert!(!caa_allows_issuance(&name("example.com"), &[vec![&issue, &critical]], "ca.example.net"));
+        assert!(caa_allows_issuance(&name("example.com"), &[vec![&issue, &noncritical]], "ca.example.net"));
+
+        let critical_issue = caa(128, "issue", "ca.example.net");
+        assert!(caa_allows_issuance(&name("example.com"), &[vec![&critical_issue]], "ca.example.net"));
+    }
+}
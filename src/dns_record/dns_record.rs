@@ -1,6 +1,31 @@
+use std::collections::HashMap;
+use ::edns::{Edns, ExtendedError};
+use ::tsig::Tsig;
 use ::enums::*;
+use ::errors::*;
+use ::labels::encode_labels;
 use ::ParsedQuestion;
-use ::dns_record::records::Record;
+use ::dns_record::records::{Record, RecordPayload, Srv};
+use ::dns_record::srv::order_targets;
+use rand::{Rng, thread_rng};
+use ::utils::{append_u16, write_u16};
+
+/// The fixed fields of a message header. The RCODE includes the upper
+/// bits carried by the OPT record, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub id: u16,
+    pub qr: QR,
+    pub opcode: OPCODE,
+    pub aa: bool,
+    pub tc: bool,
+    pub rd: bool,
+    pub ra: bool,
+    pub z: bool,
+    pub ad: bool,
+    pub cd: bool,
+    pub rcode: RCODE,
+}
 
 #[derive(Debug, PartialEq, Eq, Serialize)]
 pub struct DnsRecord<'a> {
@@ -11,26 +36,29 @@ pub struct DnsRecord<'a> {
     tc: bool,
     rd: bool,
     ra: bool,
-    dnssec: u8,
+    z: bool,
+    ad: bool,
+    cd: bool,
     rcode: RCODE,
     questions: Vec<ParsedQuestion<'a>>,
     answers: Vec<Record<'a>>,
+    authorities: Vec<Record<'a>>,
+    additionals: Vec<Record<'a>>,
+    edns: Option<Edns<'a>>,
+    tsig: Option<Tsig<'a>>,
 }
 
 impl<'a> DnsRecord<'a> {
     pub fn new(
-        id: u16,
-        qr: QR,
-        opcode: OPCODE,
-        aa: bool,
-        tc: bool,
-        rd: bool,
-        ra: bool,
-        dnssec: u8,
-        rcode: RCODE,
+        header: Header,
         questions: Vec<ParsedQuestion<'a>>,
         answers: Vec<Record<'a>>,
+        authorities: Vec<Record<'a>>,
+        additionals: Vec<Record<'a>>,
+        edns: Option<Edns<'a>>,
+        tsig: Option<Tsig<'a>>,
     ) -> Self {
+        let Header { id, qr, opcode, aa, tc, rd, ra, z, ad, cd, rcode } = header;
         DnsRecord {
             id,
             qr,
@@ -39,10 +67,16 @@ impl<'a> DnsRecord<'a> {
             tc,
             rd,
             ra,
-            dnssec,
+            z,
+            ad,
+            cd,
             rcode,
             questions,
             answers,
+            authorities,
+            additionals,
+            edns,
+            tsig,
         }
     }
 
@@ -74,10 +108,25 @@ impl<'a> DnsRecord<'a> {
         self.ra
     }
 
-    pub fn dnssec(&self) -> u8 {
-        self.dnssec
+    /// The reserved Z bit, which has to be zero in messages that
+    /// follow RFC 1035.
+    pub fn z(&self) -> bool {
+        self.z
+    }
+
+    /// Authentic Data: the responder validated all answer and authority
+    /// data with DNSSEC.
+    pub fn ad(&self) -> bool {
+        self.ad
+    }
+
+    /// Checking Disabled: the querier accepts data that has not been
+    /// validated.
+    pub fn cd(&self) -> bool {
+        self.cd
     }
 
+    /// The RCODE, including the upper bits carried by the OPT record.
     pub fn rcode(&self) -> RCODE {
         self.rcode
     }
@@ -89,4 +138,314 @@ impl<'a> DnsRecord<'a> {
     pub fn answers(&self) -> &[Record<'a>] {
         &self.answers
     }
+
+    pub fn authorities(&self) -> &[Record<'a>] {
+        &self.authorities
+    }
+
+    /// The zone section of an UPDATE message, which takes the place of
+    /// the question section.
+    pub fn zone(&self) -> &[ParsedQuestion<'a>] {
+        &self.questions
+    }
+
+    /// The prerequisite section of an UPDATE message, which takes the
+    /// place of the answer section.
+    pub fn prerequisites(&self) -> &[Record<'a>] {
+        &self.answers
+    }
+
+    /// The update section of an UPDATE message, which takes the place
+    /// of the authority section.
+    pub fn updates(&self) -> &[Record<'a>] {
+        &self.authorities
+    }
+
+    /// The records of the additional section, without the OPT record.
+    pub fn additionals(&self) -> &[Record<'a>] {
+        &self.additionals
+    }
+
+    /// The SRV records of the answer section in the order their targets
+    /// should be contacted (RFC 2782). `None` if there are no SRV
+    /// records, while an empty list means the service is decidedly not
+    /// available, as the only target is the root.
+    pub fn srv_targets(&self) -> Option<Vec<&Srv<'a>>> {
+        self.srv_targets_with(&mut thread_rng())
+    }
+
+    /// Like `srv_targets`, choosing among records of equal priority
+    /// with `rng`.
+    pub fn srv_targets_with<R: Rng>(&self, rng: &mut R) -> Option<Vec<&Srv<'a>>> {
+        let records: Vec<_> = self.answers.iter()
+            .filter_map(|record| match record.payload {
+                RecordPayload::SRV(ref srv) => Some(srv),
+                _ => None,
+            })
+            .collect();
+        if records.is_empty() {
+            return None;
+        }
+
+        let available = records.into_iter().filter(|srv| !srv.target.is_root()).collect();
+        Some(order_targets(available, rng))
+    }
+
+    /// The message's EDNS information, taken from the OPT record.
+    pub fn edns(&self) -> Option<&Edns<'a>> {
+        self.edns.as_ref()
+    }
+
+    /// The TSIG record authenticating the message, which is not part of
+    /// `additionals`.
+    pub fn tsig(&self) -> Option<&Tsig<'a>> {
+        self.tsig.as_ref()
+    }
+
+    /// The Extended DNS Errors the responder gave, if any.
+    pub fn extended_errors(&self) -> Vec<&ExtendedError<'a>> {
+        self.edns.iter()
+            .flat_map(|edns| edns.extended_errors())
+            .collect()
+    }
+
+    /// Encode the message in wire format, compressing owner names and
+    /// the names within RDATA. Parsing the result yields a message
+    /// equal to this one, names keeping their case. Fails if the
+    /// message can't be represented, e.g. with an extended RCODE but
+    /// no OPT record to carry its upper bits, or a HINFO string longer
+    /// than 255 bytes.
+    pub fn to_bytes(&self) -> Result<'static, Vec<u8>> {
+        if self.rcode.is_extended() && self.edns.is_none() {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("Extended RCODE {} requires an OPT record", self.rcode)
+            ));
+        }
+
+        let mut buffer = vec![0u8; 12];
+
+        write_u16(&mut buffer, &mut 0, self.id);
+
+        buffer[2] = (self.qr as u8) << 7;
+        buffer[2] |= ((u16::from(self.opcode) & 0x0f) as u8) << 3;
+        buffer[2] |= (self.aa as u8) << 2;
+        buffer[2] |= (self.tc as u8) << 1;
+        buffer[2] |= self.rd as u8;
+
+        buffer[3] = (self.ra as u8) << 7;
+        buffer[3] |= (self.z as u8) << 6;
+        buffer[3] |= (self.ad as u8) << 5;
+        buffer[3] |= (self.cd as u8) << 4;
+        buffer[3] |= (u16::from(self.rcode) & 0x0f) as u8;
+
+        let mut pos = 4;
+        write_u16(&mut buffer, &mut pos, self.questions.len() as u16);
+        write_u16(&mut buffer, &mut pos, self.answers.len() as u16);
+        write_u16(&mut buffer, &mut pos, self.authorities.len() as u16);
+        let arcount = self.additionals.len() + self.edns.is_some() as usize + self.tsig.is_some() as usize;
+        write_u16(&mut buffer, &mut pos, arcount as u16);
+
+        let mut encoded_labels = HashMap::new();
+        for question in &self.questions {
+            let pos = buffer.len();
+            let encoded = encode_labels(&mut encoded_labels, pos, &question.name);
+            buffer.extend(&encoded);
+
+            append_u16(&mut buffer, u16::from(question.qtype));
+            append_u16(&mut buffer, u16::from(question.qclass));
+        }
+
+        let records = self.answers.iter()
+            .chain(self.authorities.iter())
+            .chain(self.additionals.iter());
+        for record in records {
+            record.encode(&mut buffer, &mut encoded_labels)?;
+        }
+        if let Some(ref edns) = self.edns {
+            let mut edns = edns.clone();
+            edns.extended_rcode = (u16::from(self.rcode) >> 4) as u8;
+            edns.encode(&mut buffer);
+        }
+        if let Some(ref tsig) = self.tsig {
+            tsig.encode(&mut buffer);
+        }
+
+        Ok(buffer)
+    }
+
+    /// Copy all borrowed data out of the parsed message, so it can
+    /// outlive the buffer it was parsed from.
+    pub fn into_owned(self) -> DnsRecord<'static> {
+        DnsRecord {
+            id: self.id,
+            qr: self.qr,
+            opcode: self.opcode,
+            aa: self.aa,
+            tc: self.tc,
+            rd: self.rd,
+            ra: self.ra,
+            z: self.z,
+            ad: self.ad,
+            cd: self.cd,
+            rcode: self.rcode,
+            questions: self.questions.into_iter().map(ParsedQuestion::into_owned).collect(),
+            answers: self.answers.into_iter().map(Record::into_owned).collect(),
+            authorities: self.authorities.into_iter().map(Record::into_owned).collect(),
+            additionals: self.additionals.into_iter().map(Record::into_owned).collect(),
+            edns: self.edns.map(Edns::into_owned),
+            tsig: self.tsig.map(Tsig::into_owned),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::borrow::Cow;
+    use std::net::Ipv4Addr;
+    use ::parse;
+    use ::name::Name;
+    use ::dns_record::records::{Soa, Mx};
+    use ::edns::EdnsOption;
+
+    fn name(address: &str) -> Name<'static> {
+        address.parse().unwrap()
+    }
+
+    fn record(address: &str, payload: RecordPayload<'static>) -> Record<'static> {
+        Record::new(name(address), Class::IN, 300, payload)
+    }
+
+    fn message() -> DnsRecord<'static> {
+        let header = Header {
+            id: 0x1234,
+            qr: QR::RESPONSE,
+            opcode: OPCODE::QUERY,
+            aa: true,
+            tc: false,
+            rd: true,
+            ra: true,
+            z: false,
+            ad: true,
+            cd: false,
+            rcode: RCODE::Ok,
+        };
+        DnsRecord::new(
+            header,
+            vec![ParsedQuestion::new(name("www.example.com"), Qtype::MX, Qclass::IN)],
+            vec![
+                record("www.example.com", RecordPayload::CNAME(name("mail.example.com"))),
+                record("mail.example.com", RecordPayload::MX(Mx { preference: 10, exchange: name("mx.example.com") })),
+            ],
+            vec![
+                record("example.com", RecordPayload::SOA(Soa {
+                    mname: name("ns.example.com"),
+                    rname: name("hostmaster.example.com"),
+                    serial: 2018010101,
+                    refresh: 3600,
+                    retry: 600,
+                    expire: 86400,
+                    minimum: 300
+                })),
+            ],
+            vec![
+                record("mx.example.com", RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1))),
+                record("mx.example.com", RecordPayload::TXT(vec![Cow::from(&b"v=spf1 -all"[..])])),
+                record("mx.example.com", RecordPayload::Unknown(1234, Cow::from(vec![0xc0, 12]))),
+            ],
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn should_encode_losslessly() {
+        let message = message();
+        let buffer = message.to_bytes().unwrap();
+        assert_eq!(message, parse(&buffer).unwrap());
+    }
+
+    #[test]
+    fn should_compress_names_in_rdata() {
+        let buffer = message().to_bytes().unwrap();
+
+        // The question name is written at index 12, its "example.com"
+        // suffix at index 16. The answer's owner name refers to the
+        // question, the CNAME target only adds "mail".
+        let answer = 12 + 17 + 4;
+        assert_eq!(&buffer[answer..answer + 2], &[0xc0, 12]);
+        let rdata = answer + 2 + 10;
+        assert_eq!(&buffer[rdata - 2..rdata], &[0, 7]);
+        assert_eq!(&buffer[rdata..rdata + 7], &[4, b'm', b'a', b'i', b'l', 0xc0, 16]);
+    }
+
+    #[test]
+    fn should_encode_edns_losslessly() {
+        let mut edns = Edns::new(4096);
+        edns.extended_rcode = 1;
+        edns.dnssec_ok = true;
+        edns.options.push(EdnsOption::Unknown(65001, Cow::from(vec![1, 2, 3])));
+
+        let message = message();
+        let message = DnsRecord { edns: Some(edns), rcode: RCODE::BadVers, ..message };
+        let buffer = message.to_bytes().unwrap();
+        assert_eq!(&buffer[10..12], &[0, 4]);
+        assert_eq!(message, parse(&buffer).unwrap());
+    }
+
+    #[test]
+    fn should_encode_case_losslessly() {
+        let answers = vec![
+            record("www.example.com", RecordPayload::CNAME(name("www.Example.COM"))),
+            record("www.Example.COM", RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1))),
+        ];
+        let message = DnsRecord { answers, ..message() };
+        let buffer = message.to_bytes().unwrap();
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(message, result);
+        let names: Vec<_> = result.answers().iter().map(|record| record.name.to_string()).collect();
+        assert_eq!(vec!["www.example.com.", "www.Example.COM."], names);
+        match result.answers()[0].payload {
+            RecordPayload::CNAME(ref target) => assert_eq!("www.Example.COM.", target.to_string()),
+            ref payload => panic!("Unexpected payload {:?}", payload),
+        }
+    }
+
+    #[test]
+    fn should_fail_on_extended_rcode_without_edns() {
+        let message = DnsRecord { rcode: RCODE::BadVers, ..message() };
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "Extended RCODE BADVERS requires an OPT record"));
+        assert_eq!(expected, message.to_bytes());
+    }
+
+    #[test]
+    fn should_not_compress_unknown_rdata() {
+        let buffer = message().to_bytes().unwrap();
+        assert_eq!(&buffer[buffer.len() - 4..], &[0, 2, 0xc0, 12]);
+    }
+
+    fn srv(priority: u16, target: &str) -> Record<'static> {
+        record("_sip._udp.example.com", RecordPayload::SRV(Srv { priority, weight: 0, port: 5060, target: name(target) }))
+    }
+
+    #[test]
+    fn should_order_srv_targets() {
+        let answers = vec![srv(20, "b.example.com"), srv(10, "a.example.com"), srv(30, ".")];
+        let message = DnsRecord { answers, ..message() };
+
+        let targets: Vec<_> = message.srv_targets().unwrap().iter()
+            .map(|srv| srv.target.to_string())
+            .collect();
+        assert_eq!(vec!["a.example.com.", "b.example.com."], targets);
+    }
+
+    #[test]
+    fn should_tell_missing_from_unavailable_services() {
+        assert_eq!(None, message().srv_targets());
+
+        let message = DnsRecord { answers: vec![srv(0, ".")], ..message() };
+        assert_eq!(Some(vec![]), message.srv_targets());
+    }
 }
@@ -1,8 +1,16 @@
 mod question;
 mod parser;
+#[allow(clippy::module_inception)]
 mod dns_record;
 mod records;
+mod srv;
+mod svcb;
+mod caa;
 
+pub use self::dns_record::{DnsRecord, Header};
 pub use self::parser::parse;
+pub(crate) use self::parser::parse_signed;
 pub use self::question::Question;
-pub use self::records::{Record, RecordPayload};
+pub use self::records::{Record, RecordPayload, Soa, Wks, Hinfo, Minfo, Mx, Srv, Caa};
+pub use self::caa::{CaaIssuer, caa_allows_issuance};
+pub use self::svcb::{Svcb, SvcParam};
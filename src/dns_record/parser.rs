@@ -1,155 +1,246 @@
 use ::enums::*;
 use ::errors::*;
 use ::ParsedQuestion;
-use std::collections::HashSet;
-use ::dns_record::dns_record::DnsRecord;
+use ::name::{Name, MAX_LABEL_LEN, MAX_NAME_LEN};
+use ::dns_record::dns_record::{DnsRecord, Header};
+use ::edns::{Edns, EdnsOption, ClientSubnet, Cookie, ExtendedError, CLIENT_SUBNET, COOKIE, EXTENDED_ERROR, mask};
 use ::dns_record::records::Record;
-use ::dns_record::records::RecordPayload;
+use ::dns_record::records::{RecordPayload, Soa, Wks, Hinfo, Minfo, Mx, Srv, Caa};
+use ::dns_record::svcb::{Svcb, SvcParam};
+use ::tsig::Tsig;
 use num::FromPrimitive;
-use std::net::Ipv4Addr;
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str;
 
 pub fn parse<'a>(data: &'a [u8]) -> Result<'a, DnsRecord<'a>> {
+    parse_signed(data).map(|(record, _)| record)
+}
+
+/// Parse a message, also returning the index of its TSIG record, if
+/// any, as the MAC covers everything before it.
+pub(crate) fn parse_signed<'a>(data: &'a [u8]) -> Result<'a, (DnsRecord<'a>, Option<usize>)> {
+    if data.len() < 12 {
+        return Err(Error::new(
+            DnsMsgError::Truncated,
+            format!("Message of {} bytes is shorter than its header", data.len())
+        ));
+    }
+
     let mut pos = 12;
+    let opcode = opcode(data);
+    let questions = questions(data, &mut pos)?;
+    let answers = records(data, &mut pos, ancount(data)?, opcode)?;
+    let authorities = records(data, &mut pos, nscount(data)?, opcode)?;
+    let (additionals, edns, tsig) = additionals(data, &mut pos, arcount(data)?, opcode)?;
+    let (tsig_index, tsig) = match tsig {
+        Some((index, tsig)) => (Some(index), Some(tsig)),
+        None => (None, None),
+    };
+
+    let header = Header {
+        id: id(data)?,
+        qr: qr(data),
+        opcode,
+        aa: aa(data),
+        tc: tc(data),
+        rd: rd(data),
+        ra: ra(data),
+        z: z(data),
+        ad: ad(data),
+        cd: cd(data),
+        rcode: rcode(data, &edns),
+    };
+    let record = DnsRecord::new(
+        header,
+        questions,
+        answers,
+        authorities,
+        additionals,
+        edns,
+        tsig
+    );
+    Ok((record, tsig_index))
+}
 
-    Ok(DnsRecord::new(
-        id(data),
-        qr(data),
-        opcode(data)?,
-        aa(data),
-        tc(data),
-        rd(data),
-        ra(data),
-        dnssec_bits(data),
-        rcode(data)?,
-        questions(data, &mut pos)?,
-        answers(data, &mut pos)?
-    ))
-}
-
-fn read_u8<'a>(
+fn read_bytes<'a, 'b>(
     data: &'a [u8],
-    index: &mut usize
-) -> u8 {
-    assert!(*index <= data.len() - 1, "array index out of bounds!");
-    *index += 1;
-    data[*index - 1]
+    index: &mut usize,
+    len: usize
+) -> Result<'b, &'a [u8]> {
+    let bytes = data.get(*index..*index + len)
+        .ok_or(Error::new(
+            DnsMsgError::Truncated,
+            format!("Unexpected end of message reading {} bytes at index {}", len, *index)
+        ))?;
+    *index += len;
+    Ok(bytes)
 }
 
-fn read_u16<'a>(
-    data: &'a [u8],
+fn read_u8<'b>(
+    data: &[u8],
     index: &mut usize
-) -> u16 {
-    assert!(*index <= data.len() - 2, "array index out of bounds!");
-
-    let tmp = &data[*index] as *const u8;
-    let tmp = tmp as *const u16;
-    *index += 2;
-    unsafe {
-        u16::from_be(*tmp)
-    }
+) -> Result<'b, u8> {
+    let bytes = read_bytes(data, index, 1)?;
+    Ok(bytes[0])
 }
 
-fn read_u32<'a>(
-    data: &'a [u8],
+fn read_u16<'b>(
+    data: &[u8],
     index: &mut usize
-) -> u32 {
-    assert!(*index <= data.len() - 4, "array index out of bounds!");
+) -> Result<'b, u16> {
+    let bytes = read_bytes(data, index, 2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
 
-    let tmp = &data[*index] as *const u8;
-    let tmp = tmp as *const u32;
-    *index += 4;
-    unsafe {
-        u32::from_be(*tmp)
-    }
+fn read_u32<'b>(
+    data: &[u8],
+    index: &mut usize
+) -> Result<'b, u32> {
+    let bytes = read_bytes(data, index, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
 }
 
-fn id<'a>(data: &'a [u8]) -> u16 {
+// The header accessors below rely on `parse` having verified that
+// `data` holds at least the 12 header bytes.
+
+fn id<'b>(data: &[u8]) -> Result<'b, u16> {
     read_u16(data, &mut 0)
 }
 
-fn qr<'a>(data: &'a [u8]) -> QR {
+fn qr(data: &[u8]) -> QR {
     let val = data[2] >> 7;
     QR::from_u8(val).unwrap()
 }
 
-fn opcode<'a, 'b>(data: &'a [u8]) -> Result<'b, OPCODE> {
-    let val = data[2] >> 3 & 0x0f;
-    OPCODE::from_u8(val)
-        .ok_or(Error::new(DnsMsgError::InvalidData, format!("Unknown opcode {}", val)))
+fn opcode(data: &[u8]) -> OPCODE {
+    OPCODE::from(((data[2] >> 3) & 0x0f) as u16)
 }
 
-fn aa<'a>(data: &'a [u8]) -> bool {
+fn aa(data: &[u8]) -> bool {
     (data[2] & 0b_0000_0100) > 0
 }
 
-fn tc<'a>(data: &'a [u8]) -> bool {
+fn tc(data: &[u8]) -> bool {
     (data[2] & 0b_0000_0010) > 0
 }
 
-fn rd<'a>(data: &'a [u8]) -> bool {
+fn rd(data: &[u8]) -> bool {
     (data[2] & 1) > 0
 }
 
-fn ra<'a>(data: &'a [u8]) -> bool {
+fn ra(data: &[u8]) -> bool {
     (data[3] & 0b_1000_0000) > 0
 }
 
-fn dnssec_bits<'a>(data: &'a [u8]) -> u8 {
-    data[3] >> 4 & 0x0f
+fn z(data: &[u8]) -> bool {
+    (data[3] & 0b_0100_0000) > 0
+}
+
+fn ad(data: &[u8]) -> bool {
+    (data[3] & 0b_0010_0000) > 0
 }
 
-fn rcode<'a, 'b>(data: &'a [u8]) -> Result<'b, RCODE> {
-    let val = data[3] & 0x0f;
-    RCODE::from_u8(val)
-        .ok_or(Error::new(DnsMsgError::InvalidData, format!("Unknown rcode value {}", val)))
+fn cd(data: &[u8]) -> bool {
+    (data[3] & 0b_0001_0000) > 0
 }
 
-fn qdcount<'a>(data: &'a [u8]) -> u16 {
+/// The RCODE, with its upper eight bits taken from the OPT record if
+/// there is one.
+fn rcode<'a>(data: &'a [u8], edns: &Option<Edns<'a>>) -> RCODE {
+    let upper = edns.as_ref().map_or(0, |edns| edns.extended_rcode as u16);
+    RCODE::from(upper << 4 | (data[3] & 0x0f) as u16)
+}
+
+fn qdcount<'b>(data: &[u8]) -> Result<'b, u16> {
     read_u16(data, &mut 4)
 }
 
-fn ancount<'a>(data: &'a [u8]) -> u16 {
+fn ancount<'b>(data: &[u8]) -> Result<'b, u16> {
     read_u16(data, &mut 6)
 }
 
-fn nscount<'a>(data: &'a [u8]) -> u16 {
+fn nscount<'b>(data: &[u8]) -> Result<'b, u16> {
     read_u16(data, &mut 8)
 }
 
-fn arcount<'a>(data: &'a [u8]) -> u16 {
+fn arcount<'b>(data: &[u8]) -> Result<'b, u16> {
     read_u16(data, &mut 10)
 }
 
+/// Upper bound for label references followed while reading one name.
+/// A name can't have more than 127 labels, so legitimate messages never
+/// need more references than that.
+const MAX_LABEL_REFS: usize = 127;
+
+/// Read a (possibly compressed) name starting at `pos` and leave `pos`
+/// right behind it. Label references have to point backwards into the
+/// message, which rules out reference cycles.
 fn parse_labels<'a>(
     data: &'a [u8],
-    pos: &mut usize,
-    prior_jumps: &mut HashSet<usize>
-) -> Result<'a, Vec<&'a str>> {
-    if data[*pos] & 0xc0 == 0xc0 {
-        let mut jump = (read_u16(data, pos) ^ 0xc000) as usize;
-        if prior_jumps.contains(&jump) {
-            return Err(Error::new(
-                DnsMsgError::CyclicLabelRef,
-                format!("Encountered cyclic label reference at index {}", *pos)
-            ));
-        }
-        prior_jumps.insert(jump);
-        return parse_labels(data, &mut jump, prior_jumps);
-    }
-
+    pos: &mut usize
+) -> Result<'a, Vec<&'a [u8]>> {
     let mut labels = vec![];
+    let mut name_len = 1;
+    let mut refs = 0;
+    let mut cursor = *pos;
+    let mut end = None;
+
     loop {
-        let len = read_u8(data, pos) as usize;
+        let len = read_u8(data, &mut cursor)? as usize;
+
+        if len & 0xc0 == 0xc0 {
+            let ref_pos = cursor - 1;
+            let jump = (len & 0x3f) << 8 | read_u8(data, &mut cursor)? as usize;
+            if end.is_none() {
+                end = Some(cursor);
+            }
+
+            if jump >= data.len() {
+                return Err(Error::new(
+                    DnsMsgError::PointerOutOfBounds,
+                    format!("Label reference to index {} points beyond the message at index {}", jump, ref_pos)
+                ));
+            }
+            if jump >= ref_pos {
+                return Err(Error::new(
+                    DnsMsgError::CyclicLabelRef,
+                    format!("Label reference at index {} to index {} does not point backwards", ref_pos, jump)
+                ));
+            }
+            refs += 1;
+            if refs > MAX_LABEL_REFS {
+                return Err(Error::new(
+                    DnsMsgError::CyclicLabelRef,
+                    format!("Name at index {} exceeds {} label references", *pos, MAX_LABEL_REFS)
+                ));
+            }
+
+            cursor = jump;
+            continue;
+        }
+
         if len == 0 {
             break;
-        } else {
-            let lbl = str::from_utf8(&data[*pos..*pos+len]).unwrap();
-            *pos += len;
-            labels.push(lbl);
+        } else if len > MAX_LABEL_LEN {
+            return Err(Error::new(
+                DnsMsgError::LabelTooLong,
+                format!("Label length {} at index {} exceeds {} bytes", len, cursor - 1, MAX_LABEL_LEN)
+            ));
         }
+
+        name_len += len + 1;
+        if name_len > MAX_NAME_LEN {
+            return Err(Error::new(
+                DnsMsgError::NameTooLong,
+                format!("Name at index {} exceeds {} bytes", *pos, MAX_NAME_LEN)
+            ));
+        }
+
+        labels.push(read_bytes(data, &mut cursor, len)?);
     }
 
+    *pos = end.unwrap_or(cursor);
     Ok(labels)
 }
 
@@ -157,15 +248,12 @@ fn parse_question<'a>(
     data: &'a [u8],
     pos: &mut usize
 ) -> Result<'a, ParsedQuestion<'a>> {
-    let labels = parse_labels(data, pos, &mut HashSet::new())?;
-
-    let qtype = read_u16(data, pos);
-    let qtype = Qtype::from_u16(qtype).unwrap();
+    let name = parse_name(data, pos)?;
 
-    let qclass = read_u16(data, pos);
-    let qclass = Qclass::from_u16(qclass).unwrap();
+    let qtype = Qtype::from(read_u16(data, pos)?);
+    let qclass = Qclass::from(read_u16(data, pos)?);
 
-    Ok(ParsedQuestion::new(labels, qtype, qclass))
+    Ok(ParsedQuestion::new(name, qtype, qclass))
 }
 
 fn  questions<'a>(
@@ -174,51 +262,459 @@ fn  questions<'a>(
 ) -> Result<'a, Vec<ParsedQuestion<'a>>> {
     let mut questions = vec![];
 
-    for _ in 0..qdcount(data) {
+    for _ in 0..qdcount(data)? {
         questions.push(parse_question(data, pos)?);
     }
 
     Ok(questions)
 }
 
-fn parse_answer<'a>(
+fn parse_name<'a>(
     data: &'a [u8],
     pos: &mut usize
+) -> Result<'a, Name<'a>> {
+    let labels = parse_labels(data, pos)?;
+    Ok(Name::from_labels_unchecked(labels.into_iter().map(Cow::from).collect()))
+}
+
+fn parse_character_string<'a>(
+    data: &'a [u8],
+    pos: &mut usize
+) -> Result<'a, Cow<'a, [u8]>> {
+    let len = read_u8(data, pos)? as usize;
+    Ok(Cow::from(read_bytes(data, pos, len)?))
+}
+
+fn parse_record<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    opcode: OPCODE
 ) -> Result<'a, Record<'a>> {
-    let labels = parse_labels(data, pos, &mut HashSet::new())?;
-    let typ = read_u16(data, pos);
-    let typ = Type::from_u16(typ)
-        .ok_or(Error::new(DnsMsgError::InvalidData, format!("Invalid type: {}", typ)))?;
-    let class = read_u16(data, pos);
-    let class = Class::from_u16(class)
-        .ok_or(Error::new(DnsMsgError::InvalidData, format!("Invalid class: {}", class)))?;
-    let ttl = read_u32(data, pos);
-    let len = read_u16(data, pos);
+    let name = parse_name(data, pos)?;
+    let typ = Type::from(read_u16(data, pos)?);
+    let class = Class::from(read_u16(data, pos)?);
+    let ttl = read_u32(data, pos)?;
+    let len = read_u16(data, pos)? as usize;
+
+    let end = *pos + len;
+    // UPDATE messages refer to whole RRsets with class ANY or NONE and
+    // empty RDATA (RFC 2136).
+    let payload = match class {
+        Class::NONE | Class::Wildcard if len == 0 && opcode == OPCODE::UPDATE => {
+            RecordPayload::Unknown(u16::from(typ), Cow::from(&[][..]))
+        },
+        _ => parse_payload(data, pos, typ, len)?
+    };
+    if *pos != end {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type {}", len, typ)));
+    }
+
+    Ok(Record::new(name, class, ttl, payload))
+}
+
+fn parse_payload<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    typ: Type,
+    len: usize
+) -> Result<'a, RecordPayload<'a>> {
+    let end = *pos + len;
+
+    let payload = match typ {
+        Type::A => {
+            if len != 4 {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type A", len)));
+            }
+            RecordPayload::A(Ipv4Addr::from(read_u32(data, pos)?))
+        },
+        Type::NS => RecordPayload::NS(parse_name(data, pos)?),
+        Type::MD => RecordPayload::MD(parse_name(data, pos)?),
+        Type::MF => RecordPayload::MF(parse_name(data, pos)?),
+        Type::CNAME => RecordPayload::CNAME(parse_name(data, pos)?),
+        Type::SOA => RecordPayload::SOA(Soa {
+            mname: parse_name(data, pos)?,
+            rname: parse_name(data, pos)?,
+            serial: read_u32(data, pos)?,
+            refresh: read_u32(data, pos)?,
+            retry: read_u32(data, pos)?,
+            expire: read_u32(data, pos)?,
+            minimum: read_u32(data, pos)?
+        }),
+        Type::MB => RecordPayload::MB(parse_name(data, pos)?),
+        Type::MG => RecordPayload::MG(parse_name(data, pos)?),
+        Type::MR => RecordPayload::MR(parse_name(data, pos)?),
+        Type::NULL => RecordPayload::NULL(Cow::from(read_bytes(data, pos, len)?)),
+        Type::WKS => {
+            if len < 5 {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type WKS", len)));
+            }
+            let address = Ipv4Addr::from(read_u32(data, pos)?);
+            let protocol = read_u8(data, pos)?;
+            let bitmap = read_bytes(data, pos, len - 5)?;
+            RecordPayload::WKS(Wks { address, protocol, bitmap: Cow::from(bitmap) })
+        },
+        Type::PTR => RecordPayload::PTR(parse_name(data, pos)?),
+        Type::HINFO => RecordPayload::HINFO(Hinfo {
+            cpu: parse_character_string(data, pos)?,
+            os: parse_character_string(data, pos)?
+        }),
+        Type::MINFO => RecordPayload::MINFO(Minfo {
+            rmailbx: parse_name(data, pos)?,
+            emailbx: parse_name(data, pos)?
+        }),
+        Type::MX => RecordPayload::MX(Mx {
+            preference: read_u16(data, pos)?,
+            exchange: parse_name(data, pos)?
+        }),
+        Type::TXT => {
+            let mut strings = vec![];
+            while *pos < end {
+                strings.push(parse_character_string(data, pos)?);
+            }
+            RecordPayload::TXT(strings)
+        },
+        Type::AAAA => {
+            if len != 16 {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type AAAA", len)));
+            }
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(read_bytes(data, pos, len)?);
+            RecordPayload::AAAA(Ipv6Addr::from(octets))
+        },
+        Type::SRV => RecordPayload::SRV(Srv {
+            priority: read_u16(data, pos)?,
+            weight: read_u16(data, pos)?,
+            port: read_u16(data, pos)?,
+            target: parse_name(data, pos)?
+        }),
+        Type::SVCB => RecordPayload::SVCB(parse_svcb(data, pos, end)?),
+        Type::HTTPS => RecordPayload::HTTPS(parse_svcb(data, pos, end)?),
+        Type::CAA => {
+            if len < 2 {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type CAA", len)));
+            }
+            let flags = read_u8(data, pos)?;
+            let tag_len = read_u8(data, pos)? as usize;
+            let tag = read_bytes(data, pos, tag_len)?;
+            if tag.is_empty() || !tag.iter().all(u8::is_ascii_alphanumeric) || *pos > end {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("Invalid CAA tag at index {}", *pos - tag_len)));
+            }
+            let tag = Cow::from(str::from_utf8(tag).unwrap());
+            RecordPayload::CAA(Caa { flags, tag, value: Cow::from(read_bytes(data, pos, end - *pos)?) })
+        },
+        // OPT and TSIG records are only meaningful in the additional
+        // section, anywhere else they are kept as opaque data.
+        Type::OPT | Type::TSIG | Type::Wildcard | Type::Unknown(_) => RecordPayload::Unknown(u16::from(typ), Cow::from(read_bytes(data, pos, len)?))
+    };
+
+    Ok(payload)
+}
 
-    if typ != Type::A {
-        panic!("Record type not supported: {:?}", typ);
+fn parse_svcb<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    end: usize
+) -> Result<'a, Svcb<'a>> {
+    let priority = read_u16(data, pos)?;
+    let target = parse_name(data, pos)?;
+
+    let mut params = vec![];
+    while *pos < end {
+        let key = SvcParamKey::from(read_u16(data, pos)?);
+        let len = read_u16(data, pos)? as usize;
+        if *pos + len > end {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("SvcParam {} at index {} exceeds the record", key, *pos - 4)
+            ));
+        }
+        params.push(parse_svc_param(data, pos, key, len)?);
     }
 
-    if typ == Type::A && len != 4 {
-        return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type A", len)));
+    let svcb = Svcb { priority, target, params };
+    svcb.validate()?;
+    Ok(svcb)
+}
+
+fn parse_svc_param<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    key: SvcParamKey,
+    len: usize
+) -> Result<'a, SvcParam<'a>> {
+    let start = *pos;
+    let value = read_bytes(data, pos, len)?;
+    let invalid = || Error::new(DnsMsgError::InvalidData, format!("Invalid SvcParam {} at index {}", key, start));
+
+    let param = match key {
+        SvcParamKey::Mandatory if len.is_multiple_of(2) => SvcParam::Mandatory(
+            value.chunks(2).map(|key| SvcParamKey::from(u16::from(key[0]) << 8 | u16::from(key[1]))).collect()
+        ),
+        SvcParamKey::Alpn => {
+            let mut ids = vec![];
+            let mut i = 0;
+            while i < len {
+                let end = i + 1 + value[i] as usize;
+                if end > len {
+                    return Err(invalid());
+                }
+                ids.push(Cow::from(&value[i + 1..end]));
+                i = end;
+            }
+            SvcParam::Alpn(ids)
+        },
+        SvcParamKey::NoDefaultAlpn if len == 0 => SvcParam::NoDefaultAlpn,
+        SvcParamKey::Port if len == 2 => SvcParam::Port(u16::from(value[0]) << 8 | u16::from(value[1])),
+        SvcParamKey::Ipv4Hint if len.is_multiple_of(4) => SvcParam::Ipv4Hint(
+            value.chunks(4).map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])).collect()
+        ),
+        SvcParamKey::Ech => SvcParam::Ech(Cow::from(value)),
+        SvcParamKey::Ipv6Hint if len.is_multiple_of(16) => SvcParam::Ipv6Hint(
+            value.chunks(16)
+                .map(|chunk| {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(chunk);
+                    Ipv6Addr::from(octets)
+                })
+                .collect()
+        ),
+        SvcParamKey::DohPath => SvcParam::DohPath(Cow::from(str::from_utf8(value).map_err(|_| invalid())?)),
+        SvcParamKey::Unknown(key) => SvcParam::Unknown(key, Cow::from(value)),
+        _ => return Err(invalid())
+    };
+
+    Ok(param)
+}
+
+fn records<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    count: u16,
+    opcode: OPCODE
+) -> Result<'a, Vec<Record<'a>>> {
+    let mut records = vec![];
+
+    for _ in 0..count {
+        records.push(parse_record(data, pos, opcode)?);
     }
 
-    let ip = Ipv4Addr::from(read_u32(data, pos));
+    Ok(records)
+}
+
+/// The additional records, the OPT record and the TSIG record with
+/// its index.
+type Additionals<'a> = (Vec<Record<'a>>, Option<Edns<'a>>, Option<(usize, Tsig<'a>)>);
+
+/// Read the additional section, separating the OPT and TSIG
+/// pseudo-records from the other records.
+fn additionals<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    count: u16,
+    opcode: OPCODE
+) -> Result<'a, Additionals<'a>> {
+    let mut records = vec![];
+    let mut edns = None;
+    let mut tsig = None;
+
+    for i in 0..count {
+        let start = *pos;
+        let name = parse_name(data, pos)?;
+        match Type::from(read_u16(data, pos)?) {
+            Type::OPT => {
+                if !name.is_root() {
+                    return Err(Error::new(DnsMsgError::InvalidData, format!("OPT record at index {} is not owned by the root", start)));
+                }
+                if edns.is_some() {
+                    return Err(Error::new(DnsMsgError::InvalidData, format!("Second OPT record at index {}", start)));
+                }
+                edns = Some(parse_edns(data, pos)?);
+            },
+            Type::TSIG => {
+                if i != count - 1 {
+                    return Err(Error::new(DnsMsgError::InvalidData, format!("TSIG record at index {} is not the last record", start)));
+                }
+                tsig = Some((start, parse_tsig(data, pos, name)?));
+            },
+            _ => {
+                *pos = start;
+                records.push(parse_record(data, pos, opcode)?);
+            },
+        }
+    }
 
-    Ok(Record::new(labels, class, ttl, RecordPayload::A(ip)))
+    Ok((records, edns, tsig))
 }
 
-fn answers<'a>(
+/// Read the TSIG record following its owner name and type.
+fn parse_tsig<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    key_name: Name<'a>
+) -> Result<'a, Tsig<'a>> {
+    let start = *pos;
+    let class = Class::from(read_u16(data, pos)?);
+    let ttl = read_u32(data, pos)?;
+    if class != Class::Wildcard || ttl != 0 {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("TSIG record at index {} needs class ANY and a TTL of 0", start)));
+    }
+    let len = read_u16(data, pos)? as usize;
+    let end = *pos + len;
+
+    let algorithm = parse_name(data, pos)?;
+    let time_signed = ((read_u16(data, pos)? as u64) << 32) | read_u32(data, pos)? as u64;
+    let fudge = read_u16(data, pos)?;
+    let mac_len = read_u16(data, pos)? as usize;
+    let mac = read_bytes(data, pos, mac_len)?;
+    let original_id = read_u16(data, pos)?;
+    let error = RCODE::from(read_u16(data, pos)?);
+    let other_len = read_u16(data, pos)? as usize;
+    let other = read_bytes(data, pos, other_len)?;
+    if *pos != end {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("Length of {} is invalid for type TSIG", len)));
+    }
+
+    Ok(Tsig {
+        key_name,
+        algorithm,
+        time_signed,
+        fudge,
+        mac: Cow::from(mac),
+        original_id,
+        error,
+        other: Cow::from(other)
+    })
+}
+
+/// Read the OPT record following its owner name and type.
+fn parse_edns<'a>(
     data: &'a [u8],
     pos: &mut usize
-) -> Result<'a, Vec<Record<'a>>> {
-    let mut answers = vec![];
+) -> Result<'a, Edns<'a>> {
+    let udp_payload_size = read_u16(data, pos)?;
+    let ttl = read_u32(data, pos)?;
+    let len = read_u16(data, pos)? as usize;
+    let end = *pos + len;
+
+    let mut options = vec![];
+    while *pos < end {
+        let code = read_u16(data, pos)?;
+        let len = read_u16(data, pos)? as usize;
+        if *pos + len > end {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("EDNS option {} at index {} exceeds the OPT record", code, *pos - 4)
+            ));
+        }
+        options.push(parse_edns_option(data, pos, code, len)?);
+    }
+
+    Ok(Edns {
+        udp_payload_size,
+        extended_rcode: (ttl >> 24) as u8,
+        version: (ttl >> 16) as u8,
+        dnssec_ok: ttl & 0x8000 > 0,
+        z: (ttl & 0x7fff) as u16,
+        options
+    })
+}
+
+fn parse_edns_option<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    code: u16,
+    len: usize
+) -> Result<'a, EdnsOption<'a>> {
+    let option = match code {
+        CLIENT_SUBNET => EdnsOption::ClientSubnet(parse_client_subnet(data, pos, len)?),
+        COOKIE => EdnsOption::Cookie(parse_cookie(data, pos, len)?),
+        EXTENDED_ERROR => EdnsOption::ExtendedError(parse_extended_error(data, pos, len)?),
+        _ => EdnsOption::Unknown(code, Cow::from(read_bytes(data, pos, len)?))
+    };
+
+    Ok(option)
+}
+
+fn parse_extended_error<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    len: usize
+) -> Result<'a, ExtendedError<'a>> {
+    if len < 2 {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("Extended error at index {} is too short", *pos)));
+    }
+
+    let code = ExtendedErrorCode::from(read_u16(data, pos)?);
+    // the text is merely informational, so don't lose the INFO-CODE
+    // over invalid UTF-8
+    let text = String::from_utf8_lossy(read_bytes(data, pos, len - 2)?);
+    Ok(ExtendedError::new(code, text))
+}
 
-    for _ in 0..ancount(data) {
-        answers.push(parse_answer(data, pos)?);
+fn parse_cookie<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    len: usize
+) -> Result<'a, Cookie<'a>> {
+    if len != 8 && !(16..=40).contains(&len) {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("Cookie at index {} has invalid length {}", *pos, len)));
     }
 
-    Ok(answers)
+    let mut client = [0u8; 8];
+    client.copy_from_slice(read_bytes(data, pos, 8)?);
+    if len == 8 {
+        Ok(Cookie::new(client))
+    } else {
+        // the length has been checked above
+        Ok(Cookie::with_server(client, read_bytes(data, pos, len - 8)?).unwrap())
+    }
+}
+
+fn parse_client_subnet<'a>(
+    data: &'a [u8],
+    pos: &mut usize,
+    len: usize
+) -> Result<'a, ClientSubnet> {
+    let start = *pos;
+    if len < 4 {
+        return Err(Error::new(DnsMsgError::InvalidData, format!("Client subnet at index {} is too short", start)));
+    }
+
+    let family = read_u16(data, pos)?;
+    let source_prefix_len = read_u8(data, pos)?;
+    let scope_prefix_len = read_u8(data, pos)?;
+    let octets = read_bytes(data, pos, len - 4)?;
+
+    let mut address = [0u8; 16];
+    let address_len = match family {
+        1 => 4,
+        2 => 16,
+        _ => return Err(Error::new(DnsMsgError::InvalidData, format!("Unknown client subnet family {} at index {}", family, start)))
+    };
+    if source_prefix_len as usize > address_len * 8 || octets.len() != (source_prefix_len as usize).div_ceil(8) {
+        return Err(Error::new(
+            DnsMsgError::InvalidData,
+            format!("Client subnet address at index {} does not match its prefix length", start)
+        ));
+    }
+    address[..octets.len()].copy_from_slice(octets);
+    let address = if family == 1 {
+        IpAddr::V4(Ipv4Addr::new(address[0], address[1], address[2], address[3]))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(address))
+    };
+
+    if mask(address, source_prefix_len) != address {
+        return Err(Error::new(
+            DnsMsgError::InvalidData,
+            format!("Client subnet address at index {} has bits set beyond its prefix", start)
+        ));
+    }
+    ClientSubnet::with_scope(address, source_prefix_len, scope_prefix_len)
+        .map_err(|_| Error::new(
+            DnsMsgError::InvalidData,
+            format!("Client subnet scope at index {} exceeds the address length", start)
+        ))
 }
 
 #[cfg(test)]
@@ -251,7 +747,6 @@ mod test {
         let result = parse(&buffer).unwrap();
         assert_eq!(QR::QUERY, result.qr());
 
-        let mut buffer = buffer.clone();
         buffer[2] = 0b_1000_0000;
         let result = parse(&buffer).unwrap();
         assert_eq!(QR::RESPONSE, result.qr());
@@ -263,12 +758,12 @@ mod test {
 
         {
             let result = parse(&buffer).unwrap();
-            assert_eq!(false, result.aa());
+            assert!(!result.aa());
         }
 
         buffer[2] = 0b_0000_0100;
         let result = parse(&buffer).unwrap();
-        assert_eq!(true, result.aa());
+        assert!(result.aa());
     }
 
     #[test]
@@ -299,12 +794,21 @@ mod test {
             assert_eq!(OPCODE::STATUS, result.opcode());
         }
 
-        for i in 3..16 {
-            buffer[2] = i << 3;
-            let result = parse(&buffer);
-            let msg = format!("Unknown opcode {}", i);
-            assert_eq!(Err(Error::new(DnsMsgError::InvalidData, msg)), result);
+        buffer[2] = 4 << 3;
+        {
+            let result = parse(&buffer).unwrap();
+            assert_eq!(OPCODE::NOTIFY, result.opcode());
         }
+
+        buffer[2] = 5 << 3;
+        {
+            let result = parse(&buffer).unwrap();
+            assert_eq!(OPCODE::UPDATE, result.opcode());
+        }
+
+        buffer[2] = 15 << 3;
+        let result = parse(&buffer).unwrap();
+        assert_eq!(OPCODE::Unknown(15), result.opcode());
     }
 
     #[test]
@@ -313,12 +817,12 @@ mod test {
 
         {
             let result = parse(&buffer).unwrap();
-            assert_eq!(false, result.tc());
+            assert!(!result.tc());
         }
 
         buffer[2] = 0b_0000_0010;
         let result = parse(&buffer).unwrap();
-        assert_eq!(true, result.tc());
+        assert!(result.tc());
     }
 
     #[test]
@@ -327,12 +831,12 @@ mod test {
 
         {
             let result = parse(&buffer).unwrap();
-            assert_eq!(false, result.rd());
+            assert!(!result.rd());
         }
 
         buffer[2] = 1;
         let result = parse(&buffer).unwrap();
-        assert_eq!(true, result.rd());
+        assert!(result.rd());
     }
 
     #[test]
@@ -341,23 +845,57 @@ mod test {
 
         {
             let result = parse(&buffer).unwrap();
-            assert_eq!(false, result.ra());
+            assert!(!result.ra());
         }
 
         buffer[3] = 0b_1000_0000;
         let result = parse(&buffer).unwrap();
-        assert_eq!(true, result.ra());
+        assert!(result.ra());
     }
 
     #[test]
-    fn should_read_dnssec_bits() {
+    fn should_read_z_flag() {
         let mut buffer = [0u8; 512];
 
-        for i in 0..9 {
-            buffer[3] = i << 4;
+        for i in [0b_1011_0000, 0] {
+            buffer[3] = i;
             let result = parse(&buffer).unwrap();
-            assert_eq!(i, result.dnssec());
+            assert!(!result.z());
         }
+
+        buffer[3] = 0b_0100_0000;
+        let result = parse(&buffer).unwrap();
+        assert!(result.z());
+    }
+
+    #[test]
+    fn should_read_authentic_data_flag() {
+        let mut buffer = [0u8; 512];
+
+        for i in [0b_1101_0000, 0] {
+            buffer[3] = i;
+            let result = parse(&buffer).unwrap();
+            assert!(!result.ad());
+        }
+
+        buffer[3] = 0b_0010_0000;
+        let result = parse(&buffer).unwrap();
+        assert!(result.ad());
+    }
+
+    #[test]
+    fn should_read_checking_disabled_flag() {
+        let mut buffer = [0u8; 512];
+
+        for i in [0b_1110_0000, 0] {
+            buffer[3] = i;
+            let result = parse(&buffer).unwrap();
+            assert!(!result.cd());
+        }
+
+        buffer[3] = 0b_0001_0000;
+        let result = parse(&buffer).unwrap();
+        assert!(result.cd());
     }
 
     #[test]
@@ -370,7 +908,15 @@ mod test {
             (2, RCODE::ServerFailure),
             (3, RCODE::NameError),
             (4, RCODE::NotImplemented),
-            (5, RCODE::Refused)
+            (5, RCODE::Refused),
+            (6, RCODE::YXDomain),
+            (7, RCODE::YXRRSet),
+            (8, RCODE::NXRRSet),
+            (9, RCODE::NotAuth),
+            (10, RCODE::NotZone),
+            (11, RCODE::DSOTypeNI),
+            (12, RCODE::Unknown(12)),
+            (15, RCODE::Unknown(15))
         ];
 
         for (i, val) in values {
@@ -378,12 +924,6 @@ mod test {
             let result = parse(&buffer).unwrap();
             assert_eq!(val, result.rcode());
         }
-        for i in 6..16 {
-            buffer[3] = i;
-            let result = parse(&buffer);
-            let msg = format!("Unknown rcode value {}", i);
-            assert_eq!(Err(Error::new(DnsMsgError::InvalidData, msg)), result);
-        }
     }
 
     #[test]
@@ -392,18 +932,14 @@ mod test {
         buffer[5] = 1; // 1 question
 
         let mut pos = 12;
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "www.google.com"
-        );
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"www.google.com".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
-        write_u16(&mut buffer, &mut pos, Qtype::A as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::IN));
 
         let expected = ParsedQuestion::new(
-            vec!["www", "google", "com"],
+            "www.google.com".parse().unwrap(),
             Qtype::A,
             Qclass::IN
         );
@@ -418,34 +954,26 @@ mod test {
         buffer[5] = 2; // 2 questions
 
         let mut pos = 12;
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "www.google.com"
-        );
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"www.google.com".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
-        write_u16(&mut buffer, &mut pos, Qtype::A as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::IN));
 
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "www.heise.de"
-        );
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"www.heise.de".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
-        write_u16(&mut buffer, &mut pos, Qtype::NS as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::CS as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::NS));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::CS));
 
         let expected = vec![
             ParsedQuestion::new(
-                vec!["www", "google", "com"],
+                "www.google.com".parse().unwrap(),
                 Qtype::A,
                 Qclass::IN
             ),
             ParsedQuestion::new(
-                vec!["www", "heise", "de"],
+                "www.heise.de".parse().unwrap(),
                 Qtype::NS,
                 Qclass::CS
             )
@@ -461,29 +989,25 @@ mod test {
         buffer[5] = 2; // 2 questions
 
         let mut pos = 12;
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "google.com"
-        );
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"google.com".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
 
-        write_u16(&mut buffer, &mut pos, Qtype::A as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::IN));
 
         write_u16(&mut buffer, &mut pos, 0xc000 | 12); // ref 12
-        write_u16(&mut buffer, &mut pos, Qtype::NS as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::CH as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::NS));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::CH));
 
         let expected = vec![
             ParsedQuestion::new(
-                vec!["google", "com"],
+                "google.com".parse().unwrap(),
                 Qtype::A,
                 Qclass::IN
             ),
             ParsedQuestion::new(
-                vec!["google", "com"],
+                "google.com".parse().unwrap(),
                 Qtype::NS,
                 Qclass::CH
             )
@@ -494,42 +1018,189 @@ mod test {
     }
 
     #[test]
-    fn should_recognize_cyclic_label_refs() {
+    fn should_reject_forward_label_refs() {
         let mut buffer = [0u8; 512];
         buffer[5] = 2; // 2 questions
 
         buffer[12] = 0xc0;
         let mut pos = 14;
-        write_u16(&mut buffer, &mut pos, Qtype::A as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::IN));
 
         buffer[13] = pos as u8;
         buffer[pos] = 0xc0;
         buffer[pos + 1] = 12;
         pos += 2;
-        write_u16(&mut buffer, &mut pos, Qtype::A as u16);
-        write_u16(&mut buffer, &mut pos, Qclass::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Qtype::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Qclass::IN));
+
+        let result = parse(&buffer);
+        let expected = Err(Error::new(DnsMsgError::CyclicLabelRef, "Label reference at index 12 to index 18 does not point backwards"));
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn should_reject_self_label_refs() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        buffer.extend(&[3, b'w', b'w', b'w', 0xc0, 16, 0, 1, 0, 1]);
 
         let result = parse(&buffer);
-        let expected = Err(Error::new(DnsMsgError::CyclicLabelRef, "Encountered cyclic label reference at index 14"));
+        let expected = Err(Error::new(DnsMsgError::CyclicLabelRef, "Label reference at index 16 to index 16 does not point backwards"));
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn should_read_labels_followed_by_label_ref() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 2; // 2 questions
+        buffer.extend(&[6, b'g', b'o', b'o', b'g', b'l', b'e', 3, b'c', b'o', b'm', 0, 0, 1, 0, 1]);
+        buffer.extend(&[3, b'w', b'w', b'w', 0xc0, 12, 0, 28, 0, 1]);
+
+        let expected = vec![
+            ParsedQuestion::new("google.com".parse().unwrap(), Qtype::A, Qclass::IN),
+            ParsedQuestion::new("www.google.com".parse().unwrap(), Qtype::AAAA, Qclass::IN)
+        ];
+        assert_eq!(expected, parse(&buffer).unwrap().questions());
+    }
+
+    #[test]
+    fn should_read_chained_label_refs() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 3; // 3 questions
+        // com
+        buffer.extend(&[3, b'c', b'o', b'm', 0, 0, 1, 0, 1]);
+        // google + ref to com
+        buffer.extend(&[6, b'g', b'o', b'o', b'g', b'l', b'e', 0xc0, 12, 0, 1, 0, 1]);
+        // mail + ref to google.com
+        buffer.extend(&[4, b'm', b'a', b'i', b'l', 0xc0, 21, 0, 15, 0, 1]);
+
+        let expected = vec![
+            ParsedQuestion::new("com".parse().unwrap(), Qtype::A, Qclass::IN),
+            ParsedQuestion::new("google.com".parse().unwrap(), Qtype::A, Qclass::IN),
+            ParsedQuestion::new("mail.google.com".parse().unwrap(), Qtype::MX, Qclass::IN)
+        ];
+        assert_eq!(expected, parse(&buffer).unwrap().questions());
+    }
+
+    #[test]
+    fn should_limit_label_refs_per_name() {
+        let mut buffer = vec![0u8; 12];
+        buffer[7] = 2; // 2 answers
+
+        // an opaque record whose rdata is a chain of 128 label refs,
+        // each pointing at the previous one and the first at the root
+        buffer.extend(&[0, 0x04, 0xd2, 0, 1, 0, 0, 0, 32, 1, 0]);
+        let mut previous = 12;
+        for _ in 0..128 {
+            let pos = buffer.len();
+            buffer.extend(&[0xc0 | (previous >> 8) as u8, previous as u8]);
+            previous = pos;
+        }
+
+        // a record named by the last ref of the chain
+        buffer.extend(&[0xc0 | (previous >> 8) as u8, previous as u8]);
+        buffer.extend(&[0, 1, 0, 1, 0, 0, 0, 32, 0, 4, 1, 1, 1, 1]);
+
+        let result = parse(&buffer);
+        let msg = format!("Name at index {} exceeds 127 label references", previous + 2);
+        assert_eq!(Err(Error::new(DnsMsgError::CyclicLabelRef, msg)), result);
+    }
+
+    #[test]
+    fn should_fail_on_truncated_header() {
+        for len in 0..12 {
+            let buffer = vec![0u8; len];
+            let msg = format!("Message of {} bytes is shorter than its header", len);
+            assert_eq!(Err(Error::new(DnsMsgError::Truncated, msg)), parse(&buffer));
+        }
+    }
+
+    #[test]
+    fn should_fail_on_truncated_question() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        buffer.extend(&[6, b'g', b'o', b'o']);
+
+        let result = parse(&buffer);
+        let expected = Err(Error::new(DnsMsgError::Truncated, "Unexpected end of message reading 6 bytes at index 13"));
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn should_fail_on_label_refs_out_of_bounds() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        buffer.extend(&[0xc0, 0xff, 0, 1, 0, 1]);
+
+        let result = parse(&buffer);
+        let expected = Err(Error::new(
+            DnsMsgError::PointerOutOfBounds,
+            "Label reference to index 255 points beyond the message at index 12"
+        ));
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn should_fail_on_labels_too_long() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        buffer.push(64);
+        buffer.extend(&[b'a'; 64]);
+        buffer.extend(&[0, 0, 1, 0, 1]);
+
+        let result = parse(&buffer);
+        let expected = Err(Error::new(DnsMsgError::LabelTooLong, "Label length 64 at index 12 exceeds 63 bytes"));
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn should_fail_on_names_too_long() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        for _ in 0..4 {
+            buffer.push(63);
+            buffer.extend(&[b'a'; 63]);
+        }
+        buffer.extend(&[0, 0, 1, 0, 1]);
+
+        let result = parse(&buffer);
+        let expected = Err(Error::new(DnsMsgError::NameTooLong, "Name at index 12 exceeds 255 bytes"));
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn should_read_binary_labels() {
+        let mut buffer = vec![0u8; 12];
+        buffer[5] = 1; // 1 question
+        buffer.extend(&[3, 0xc3, b'.', 0, 0, 0, 1, 0, 1]);
+
+        let result = parse(&buffer).unwrap();
+        let name = &result.questions()[0].name;
+        assert_eq!(vec![&[0xc3, b'.', 0][..]], name.labels().collect::<Vec<_>>());
+        assert_eq!("\\195\\.\\000.", name.to_string());
+    }
+
+    #[test]
+    fn should_fail_on_truncated_rdata() {
+        let buffer = answer_buffer(Type::AAAA, &[0u8; 16]);
+
+        // header + name + type, class, ttl, len + half of the address
+        let result = parse(&buffer[..12 + 12 + 10 + 8]);
+        assert_eq!(DnsMsgError::Truncated, result.unwrap_err().kind);
+    }
+
     #[test]
     fn should_read_arecord_answers() {
         let mut buffer = [0u8; 512];
         write_u16(&mut buffer, &mut 6, 1);
 
         let mut pos = 12;
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "google.com"
-        );
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"google.com".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
-        write_u16(&mut buffer, &mut pos, Type::A as u16);
-        write_u16(&mut buffer, &mut pos, Class::IN as u16);
+        write_u16(&mut buffer, &mut pos, u16::from(Type::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Class::IN));
         write_u32(&mut buffer, &mut pos, 32); // TTL
         write_u16(&mut buffer, &mut pos, 4); // len
 
@@ -537,7 +1208,7 @@ mod test {
 
         let result = parse(&buffer).unwrap();
         let expectation = Record::new(
-            vec!["google", "com"],
+            "google.com".parse().unwrap(),
             Class::IN,
             32,
             RecordPayload::A(Ipv4Addr::new(8, 16, 32, 64))
@@ -546,38 +1217,567 @@ mod test {
         assert_eq!(vec![expectation], result.answers());
     }
 
+    #[test]
+    fn should_read_authority_and_additional_records() {
+        let mut buffer = [0u8; 512];
+        write_u16(&mut buffer, &mut 6, 1); // 1 answer
+        write_u16(&mut buffer, &mut 8, 1); // 1 authority
+        write_u16(&mut buffer, &mut 10, 2); // 2 additionals
+
+        let mut pos = 12;
+        let mut map = HashMap::new();
+        let records = [
+            ("google.com", [1, 1, 1, 1]),
+            ("ns1.google.com", [2, 2, 2, 2]),
+            ("ns2.google.com", [3, 3, 3, 3]),
+            ("ns3.google.com", [4, 4, 4, 4]),
+        ];
+        for &(name, ip) in records.iter() {
+            let encoded_labels = encode_labels(&mut map, pos, &name.parse().unwrap());
+            buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
+            pos += encoded_labels.len();
+            write_u16(&mut buffer, &mut pos, u16::from(Type::A));
+            write_u16(&mut buffer, &mut pos, u16::from(Class::IN));
+            write_u32(&mut buffer, &mut pos, 32); // TTL
+            write_u16(&mut buffer, &mut pos, 4); // len
+            buffer[pos..pos+4].copy_from_slice(&ip);
+            pos += 4;
+        }
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![Record::new("google.com".parse().unwrap(), Class::IN, 32, RecordPayload::A(Ipv4Addr::new(1, 1, 1, 1)))],
+            result.answers()
+        );
+        assert_eq!(
+            vec![Record::new("ns1.google.com".parse().unwrap(), Class::IN, 32, RecordPayload::A(Ipv4Addr::new(2, 2, 2, 2)))],
+            result.authorities()
+        );
+        assert_eq!(
+            vec![
+                Record::new("ns2.google.com".parse().unwrap(), Class::IN, 32, RecordPayload::A(Ipv4Addr::new(3, 3, 3, 3))),
+                Record::new("ns3.google.com".parse().unwrap(), Class::IN, 32, RecordPayload::A(Ipv4Addr::new(4, 4, 4, 4)))
+            ],
+            result.additionals()
+        );
+    }
+
     #[test]
     fn should_fail_on_invalid_arecord_data() {
         let mut buffer = [0u8; 128];
         write_u16(&mut buffer, &mut 6, 1);
 
         let mut pos = 12;
-        let encoded_labels = encode_labels(
-            &mut HashMap::new(),
-            pos,
-            "google.com"
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"google.com".parse().unwrap());
+        buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
+        pos += encoded_labels.len();
+
+        write_u16(&mut buffer, &mut pos, u16::from(Type::A));
+        write_u16(&mut buffer, &mut pos, u16::from(Class::IN));
+        write_u32(&mut buffer, &mut pos, 32); // TTL
+        write_u16(&mut buffer, &mut pos, 5); // len
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Length of 5 is invalid for type A")), parse(&buffer));
+    }
+
+    #[test]
+    fn should_read_unknown_types_and_classes() {
+        let mut buffer = [0u8; 128];
+        write_u16(&mut buffer, &mut 6, 1);
+
+        let mut pos = 12;
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"google.com".parse().unwrap());
+        buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
+        pos += encoded_labels.len();
+        write_u16(&mut buffer, &mut pos, 1234);
+        write_u16(&mut buffer, &mut pos, 42);
+        write_u32(&mut buffer, &mut pos, 32); // TTL
+        write_u16(&mut buffer, &mut pos, 3); // len
+        buffer[pos..pos+3].copy_from_slice(&[1, 2, 3]);
+
+        let result = parse(&buffer).unwrap();
+        let expectation = Record::new(
+            "google.com".parse().unwrap(),
+            Class::Unknown(42),
+            32,
+            RecordPayload::Unknown(1234, Cow::from(&[1u8, 2, 3][..]))
         );
+        assert_eq!(vec![expectation], result.answers());
+        assert_eq!(Type::Unknown(1234), result.answers()[0].payload.typ());
+    }
+
+    #[test]
+    fn should_read_questions_with_unknown_qtype_and_qclass() {
+        let mut buffer = [0u8; 128];
+        buffer[5] = 1; // 1 question
+
+        let mut pos = 12;
+        let encoded_labels = encode_labels(&mut HashMap::new(), pos, &"google.com".parse().unwrap());
         buffer[pos..pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
         pos += encoded_labels.len();
+        write_u16(&mut buffer, &mut pos, 1234);
+        write_u16(&mut buffer, &mut pos, 254);
 
-        {
-            write_u16(&mut buffer, &mut pos, 18);
-            assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid type: 18")), parse(&buffer));
-        }
+        let expected = ParsedQuestion::new(
+            "google.com".parse().unwrap(),
+            Qtype::Unknown(1234),
+            Qclass::Unknown(254)
+        );
+        assert_eq!(vec![expected], parse(&buffer).unwrap().questions());
+    }
 
-        {
-            pos -= 2;
-            write_u16(&mut buffer, &mut pos, Type::A as u16);
-            write_u16(&mut buffer, &mut pos, 5);
-            assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid class: 5")), parse(&buffer));
+    fn write_record(
+        buffer: &mut [u8],
+        pos: &mut usize,
+        address: &str,
+        typ: Type,
+        rdata: &[u8]
+    ) {
+        let encoded_labels = encode_labels(&mut HashMap::new(), *pos, &address.parse().unwrap());
+        buffer[*pos..*pos+encoded_labels.len()].copy_from_slice(&encoded_labels);
+        *pos += encoded_labels.len();
+        write_u16(buffer, pos, u16::from(typ));
+        write_u16(buffer, pos, u16::from(Class::IN));
+        write_u32(buffer, pos, 32); // TTL
+        write_u16(buffer, pos, rdata.len() as u16);
+        buffer[*pos..*pos+rdata.len()].copy_from_slice(rdata);
+        *pos += rdata.len();
+    }
+
+    fn opt_buffer(opt: &[u8]) -> Vec<u8> {
+        let mut buffer = vec![0u8; 12];
+        buffer[11] = 1; // 1 additional record
+        buffer.extend(opt);
+        buffer
+    }
+
+    fn answer_buffer(typ: Type, rdata: &[u8]) -> [u8; 512] {
+        let mut buffer = [0u8; 512];
+        write_u16(&mut buffer, &mut 6, 1);
+        write_record(&mut buffer, &mut 12, "google.com", typ, rdata);
+        buffer
+    }
+
+    fn name(address: &str) -> Vec<u8> {
+        encode_labels(&mut HashMap::new(), 0, &address.parse().unwrap())
+    }
+
+    #[test]
+    fn should_read_name_records() {
+        type Constructor = fn(Name<'static>) -> RecordPayload<'static>;
+
+        let rdata = name("ns1.google.com");
+        let payloads: Vec<(Type, Constructor)> = vec![
+            (Type::NS, RecordPayload::NS),
+            (Type::MD, RecordPayload::MD),
+            (Type::MF, RecordPayload::MF),
+            (Type::CNAME, RecordPayload::CNAME),
+            (Type::MB, RecordPayload::MB),
+            (Type::MG, RecordPayload::MG),
+            (Type::MR, RecordPayload::MR),
+            (Type::PTR, RecordPayload::PTR),
+        ];
+
+        for (typ, payload) in payloads {
+            let buffer = answer_buffer(typ, &rdata);
+            let expected = payload("ns1.google.com".parse().unwrap());
+            assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
         }
+    }
 
-        {
-            pos -= 2;
-            write_u16(&mut buffer, &mut pos, Class::IN as u16);
-            write_u32(&mut buffer, &mut pos, 32); // TTL
-            write_u16(&mut buffer, &mut pos, 5); // len
-            assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Length of 5 is invalid for type A")), parse(&buffer));
+    #[test]
+    fn should_read_compressed_names_in_rdata() {
+        let mut buffer = [0u8; 512];
+        write_u16(&mut buffer, &mut 6, 1);
+        write_record(&mut buffer, &mut 12, "google.com", Type::CNAME, &[0xc0, 12]);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            RecordPayload::CNAME("google.com".parse().unwrap()),
+            result.answers()[0].payload
+        );
+    }
+
+    #[test]
+    fn should_read_soa_records() {
+        let mut rdata = name("ns1.google.com");
+        rdata.extend(name("dns-admin.google.com"));
+        rdata.extend(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 5]);
+
+        let expected = RecordPayload::SOA(Soa {
+            mname: "ns1.google.com".parse().unwrap(),
+            rname: "dns-admin.google.com".parse().unwrap(),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 5
+        });
+        let buffer = answer_buffer(Type::SOA, &rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_mx_records() {
+        let mut rdata = vec![0, 10];
+        rdata.extend(name("smtp.google.com"));
+
+        let expected = RecordPayload::MX(Mx {
+            preference: 10,
+            exchange: "smtp.google.com".parse().unwrap()
+        });
+        let buffer = answer_buffer(Type::MX, &rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_minfo_records() {
+        let mut rdata = name("admin.google.com");
+        rdata.extend(name("errors.google.com"));
+
+        let expected = RecordPayload::MINFO(Minfo {
+            rmailbx: "admin.google.com".parse().unwrap(),
+            emailbx: "errors.google.com".parse().unwrap()
+        });
+        let buffer = answer_buffer(Type::MINFO, &rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_txt_and_hinfo_records() {
+        let rdata = b"\x05hello\x00\x05world";
+        let expected = RecordPayload::TXT(vec![Cow::from(&b"hello"[..]), Cow::from(&b""[..]), Cow::from(&b"world"[..])]);
+        let buffer = answer_buffer(Type::TXT, rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+
+        let rdata = b"\x02\xff\x00\x07v=DKIM1";
+        let expected = RecordPayload::TXT(vec![Cow::from(&b"\xff\x00"[..]), Cow::from(&b"v=DKIM1"[..])]);
+        let buffer = answer_buffer(Type::TXT, rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+
+        let rdata = b"\x03x86\x05Linux";
+        let expected = RecordPayload::HINFO(Hinfo { cpu: Cow::from(&b"x86"[..]), os: Cow::from(&b"Linux"[..]) });
+        let buffer = answer_buffer(Type::HINFO, rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_null_and_wks_records() {
+        let rdata = [0xde, 0xad, 0xbe, 0xef];
+        let expected = RecordPayload::NULL(Cow::from(&rdata[..]));
+        let buffer = answer_buffer(Type::NULL, &rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+
+        let rdata = [10, 0, 0, 1, 6, 0b_0000_0000, 0b_0100_0000];
+        let expected = RecordPayload::WKS(Wks {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            protocol: 6,
+            bitmap: Cow::from(&rdata[5..])
+        });
+        let buffer = answer_buffer(Type::WKS, &rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_aaaa_records() {
+        let rdata = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let buffer = answer_buffer(Type::AAAA, &rdata);
+
+        let expected = RecordPayload::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_fail_on_invalid_aaaa_record_length() {
+        let buffer = answer_buffer(Type::AAAA, &[0u8; 4]);
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Length of 4 is invalid for type AAAA")), parse(&buffer));
+    }
+
+    #[test]
+    fn should_read_svcb_and_https_records() {
+        let mut rdata = vec![0, 1];
+        rdata.extend(name("svc.google.com"));
+        rdata.extend(&[0, 0, 0, 2, 0, 3]);
+        rdata.extend(&[0, 1, 0, 6, 2, b'h', b'2', 2, b'h', b'3']);
+        rdata.extend(&[0, 3, 0, 2, 0x20, 0xfb]);
+        rdata.extend(&[0, 6, 0, 16, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let expected = Svcb {
+            priority: 1,
+            target: "svc.google.com".parse().unwrap(),
+            params: vec![
+                SvcParam::Mandatory(vec![SvcParamKey::Port]),
+                SvcParam::Alpn(vec![Cow::from(&b"h2"[..]), Cow::from(&b"h3"[..])]),
+                SvcParam::Port(8443),
+                SvcParam::Ipv6Hint(vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)])
+            ]
+        };
+        let buffer = answer_buffer(Type::HTTPS, &rdata);
+        assert_eq!(RecordPayload::HTTPS(expected.clone()), parse(&buffer).unwrap().answers()[0].payload);
+        let buffer = answer_buffer(Type::SVCB, &rdata);
+        assert_eq!(RecordPayload::SVCB(expected), parse(&buffer).unwrap().answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_caa_records() {
+        let rdata = b"\x80\x05issueca.example.net; account=230123";
+        let expected = RecordPayload::CAA(Caa {
+            flags: 128,
+            tag: Cow::from("issue"),
+            value: Cow::from(&b"ca.example.net; account=230123"[..])
+        });
+        let buffer = answer_buffer(Type::CAA, rdata);
+        assert_eq!(expected, parse(&buffer).unwrap().answers()[0].payload);
+
+        let buffer = answer_buffer(Type::CAA, b"\x00\x05iodef");
+        assert_eq!(
+            RecordPayload::CAA(Caa { flags: 0, tag: Cow::from("iodef"), value: Cow::from(&b""[..]) }),
+            parse(&buffer).unwrap().answers()[0].payload
+        );
+    }
+
+    #[test]
+    fn should_fail_on_invalid_caa_tags() {
+        let buffer = answer_buffer(Type::CAA, b"\x00\x00ca.example.net");
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid CAA tag at index 36")), parse(&buffer));
+        let buffer = answer_buffer(Type::CAA, b"\x00\x05is-uex");
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid CAA tag at index 36")), parse(&buffer));
+        let buffer = answer_buffer(Type::CAA, b"\x00\x09issue");
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid CAA tag at index 36")), parse(&buffer));
+    }
+
+    #[test]
+    fn should_fail_on_invalid_svc_params() {
+        let svcb = |params: &[u8]| {
+            let mut rdata = vec![0, 1, 0];
+            rdata.extend(params);
+            answer_buffer(Type::HTTPS, &rdata)
+        };
+
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "SvcParam alpn has to come before port")),
+            parse(&svcb(&[0, 3, 0, 2, 1, 187, 0, 1, 0, 3, 2, b'h', b'2']))
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Mandatory SvcParam port is missing")),
+            parse(&svcb(&[0, 0, 0, 2, 0, 3]))
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Invalid SvcParam port at index 41")),
+            parse(&svcb(&[0, 3, 0, 1, 1]))
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Invalid SvcParam alpn at index 41")),
+            parse(&svcb(&[0, 1, 0, 3, 3, b'h', b'2']))
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "SvcParam ipv4hint at index 37 exceeds the record")),
+            parse(&svcb(&[0, 4, 0, 8, 10, 0, 0, 1]))
+        );
+    }
+
+    #[test]
+    fn should_fail_on_rdata_length_mismatch() {
+        let mut buffer = [0u8; 512];
+        write_u16(&mut buffer, &mut 6, 1);
+        let mut rdata = vec![0, 10];
+        rdata.extend(name("smtp.google.com"));
+        rdata.push(0);
+        write_record(&mut buffer, &mut 12, "google.com", Type::MX, &rdata);
+
+        let result = parse(&buffer);
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Length of 20 is invalid for type MX")), result);
+    }
+
+    #[test]
+    fn should_combine_rcode_with_opt_record() {
+        let mut buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, // root, OPT, 4096 bytes
+            1, 0, 0, 0, // extended rcode 1, version 0
+            0, 0
+        ]);
+        buffer[3] = 7;
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(RCODE::BadCookie, result.rcode());
+    }
+
+    #[test]
+    fn should_read_edns() {
+        let buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, // root, OPT, 4096 bytes
+            1, 0, 0x80, 0x01, // extended rcode, version, DO and z bits
+            0, 10, 0xfd, 0xe9, 0, 2, 0xab, 0xcd, 0xff, 0xff, 0, 0
+        ]);
+
+        let result = parse(&buffer).unwrap();
+        assert!(result.additionals().is_empty());
+
+        let edns = result.edns().unwrap();
+        assert_eq!(4096, edns.udp_payload_size);
+        assert_eq!(1, edns.extended_rcode);
+        assert_eq!(0, edns.version);
+        assert!(edns.dnssec_ok);
+        assert_eq!(1, edns.z);
+        assert_eq!(
+            vec![
+                EdnsOption::Unknown(65001, Cow::from(&[0xab, 0xcd][..])),
+                EdnsOption::Unknown(0xffff, Cow::from(&[][..])),
+            ],
+            edns.options
+        );
+    }
+
+    #[test]
+    fn should_keep_other_additional_records() {
+        let mut buffer = [0u8; 512];
+        write_u16(&mut buffer, &mut 10, 2);
+        let mut pos = 12;
+        write_record(&mut buffer, &mut pos, "google.com", Type::A, &[127, 0, 0, 1]);
+        buffer[pos..pos + 11].copy_from_slice(&[0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 0]);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(1, result.additionals().len());
+        assert_eq!(512, result.edns().unwrap().udp_payload_size);
+        assert_eq!(None, parse(&[0u8; 12]).unwrap().edns());
+    }
+
+    #[test]
+    fn should_fail_on_invalid_opt_records() {
+        let buffer = opt_buffer(&[1, b'a', 0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "OPT record at index 12 is not owned by the root"));
+        assert_eq!(expected, parse(&buffer));
+
+        let mut buffer = opt_buffer(&[0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 0]);
+        buffer[11] = 2;
+        buffer.extend(&[0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "Second OPT record at index 23"));
+        assert_eq!(expected, parse(&buffer));
+
+        let buffer = opt_buffer(&[0, 0, 41, 2, 0, 0, 0, 0, 0, 0, 5, 0, 10, 0, 2, 0xab]);
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "EDNS option 10 at index 23 exceeds the OPT record"));
+        assert_eq!(expected, parse(&buffer));
+    }
+
+    #[test]
+    fn should_keep_opt_records_outside_the_additional_section_opaque() {
+        let buffer = answer_buffer(Type::OPT, &[0, 10, 0, 0]);
+        let result = parse(&buffer).unwrap();
+        assert_eq!(RecordPayload::Unknown(41, Cow::from(&[0, 10, 0, 0][..])), result.answers()[0].payload);
+    }
+
+    #[test]
+    fn should_read_client_subnets() {
+        let buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 18,
+            0, 8, 0, 7, 0, 1, 23, 0, 192, 0, 2,
+            0, 8, 0, 3, 0, 2, 0, 0
+        ]);
+        // the last option is cut short
+        assert!(parse(&buffer).is_err());
+
+        let buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 19,
+            0, 8, 0, 7, 0, 1, 23, 0, 192, 0, 2,
+            0, 8, 0, 4, 0, 2, 0, 48
+        ]);
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![
+                EdnsOption::ClientSubnet(ClientSubnet::new("192.0.2.0".parse().unwrap(), 23).unwrap()),
+                EdnsOption::ClientSubnet(ClientSubnet::with_scope("::".parse().unwrap(), 0, 48).unwrap()),
+            ],
+            result.edns().unwrap().options
+        );
+    }
+
+    #[test]
+    fn should_fail_on_invalid_client_subnets() {
+        let ecs = |option: &[u8]| {
+            let mut opt = vec![0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, option.len() as u8 + 4, 0, 8, 0, option.len() as u8];
+            opt.extend(option);
+            parse(&opt_buffer(&opt)).map(|_| ()).map_err(|e| e.msg.into_owned())
+        };
+
+        assert_eq!(Err("Client subnet at index 27 is too short".to_owned()), ecs(&[0, 1, 0]));
+        assert_eq!(Err("Unknown client subnet family 3 at index 27".to_owned()), ecs(&[0, 3, 0, 0]));
+        assert_eq!(
+            Err("Client subnet address at index 27 does not match its prefix length".to_owned()),
+            ecs(&[0, 1, 24, 0, 192, 0])
+        );
+        assert_eq!(
+            Err("Client subnet address at index 27 does not match its prefix length".to_owned()),
+            ecs(&[0, 1, 33, 0, 192, 0, 2, 0, 0])
+        );
+        assert_eq!(
+            Err("Client subnet address at index 27 has bits set beyond its prefix".to_owned()),
+            ecs(&[0, 1, 23, 0, 192, 0, 3])
+        );
+        assert_eq!(
+            Err("Client subnet scope at index 27 exceeds the address length".to_owned()),
+            ecs(&[0, 1, 24, 33, 192, 0, 2])
+        );
+        assert_eq!(Ok(()), ecs(&[0, 1, 24, 24, 192, 0, 2]));
+    }
+
+    #[test]
+    fn should_read_cookies() {
+        let buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 40,
+            0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8,
+            0, 10, 0, 24, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2
+        ]);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![
+                EdnsOption::Cookie(Cookie::new([1, 2, 3, 4, 5, 6, 7, 8])),
+                EdnsOption::Cookie(Cookie::with_server([1; 8], vec![2; 16]).unwrap()),
+            ],
+            result.edns().unwrap().options
+        );
+    }
+
+    #[test]
+    fn should_fail_on_invalid_cookie_lengths() {
+        for &len in &[0u8, 7, 9, 15, 41] {
+            let mut opt = vec![0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, len + 4, 0, 10, 0, len];
+            opt.extend(vec![0; len as usize]);
+            let expected = Err(Error::new(DnsMsgError::InvalidData, format!("Cookie at index 27 has invalid length {}", len)));
+            assert_eq!(expected, parse(&opt_buffer(&opt)));
         }
     }
+
+    #[test]
+    fn should_read_extended_errors() {
+        let buffer = opt_buffer(&[
+            0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 18,
+            0, 15, 0, 2, 0, 22,
+            0, 15, 0, 8, 0, 6, b's', b'i', b'g', b' ', 0xc3, 0xa4
+        ]);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(
+            vec![
+                &ExtendedError::new(ExtendedErrorCode::NoReachableAuthority, ""),
+                &ExtendedError::new(ExtendedErrorCode::DnssecBogus, "sig \u{e4}"),
+            ],
+            result.extended_errors()
+        );
+    }
+
+    #[test]
+    fn should_fail_on_invalid_extended_errors() {
+        let buffer = opt_buffer(&[0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 5, 0, 15, 0, 1, 0]);
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "Extended error at index 27 is too short"));
+        assert_eq!(expected, parse(&buffer));
+    }
+
+    #[test]
+    fn should_replace_invalid_extended_error_text() {
+        let buffer = opt_buffer(&[0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 8, 0, 15, 0, 4, 0, 6, b'x', 0xff]);
+        assert_eq!(
+            vec![&ExtendedError::new(ExtendedErrorCode::DnssecBogus, "x\u{fffd}")],
+            parse(&buffer).unwrap().extended_errors()
+        );
+    }
 }
@@ -1,25 +1,27 @@
 use ::enums::{Qtype, Qclass};
-use std::borrow::Cow;
+use ::name::Name;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Question<'a> {
-    pub labels: Vec<Cow<'a, str>>,
+    pub name: Name<'a>,
     pub qtype: Qtype,
     pub qclass: Qclass
 }
 
 impl<'a> Question<'a> {
-    pub fn new<T>(
-        labels: T,
+    pub fn new(
+        name: Name<'a>,
         qtype: Qtype,
         qclass: Qclass
-    ) -> Question<'a>
-    where T: IntoIterator,
-          T::Item: Into<Cow<'a, str>>
-    {
-        let labels = labels.into_iter()
-            .map(|t| t.into())
-            .collect();
-        Question { labels, qtype, qclass }
+    ) -> Question<'a> {
+        Question { name, qtype, qclass }
+    }
+
+    pub fn into_owned(self) -> Question<'static> {
+        Question {
+            name: self.name.into_owned(),
+            qtype: self.qtype,
+            qclass: self.qclass
+        }
     }
 }
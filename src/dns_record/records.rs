@@ -1,33 +1,654 @@
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::borrow::Cow;
-use ::enums::Class;
+use std::collections::HashMap;
+use std::fmt;
+use std::result;
+use serde::{Serialize, Serializer};
+use ::enums::{Class, Type};
+use ::errors::*;
+use ::labels::encode_labels;
+use ::name::Name;
+use ::dns_record::svcb::Svcb;
+use ::utils::{append_u16, append_u32, write_u16};
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
+const MAX_CHARACTER_STRING_LEN: usize = 255;
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub struct Record<'a> {
-    pub labels: Vec<Cow<'a, str>>,
+    pub name: Name<'a>,
     pub class: Class,
     pub ttl: u32,
-    pub payload: RecordPayload
+    pub payload: RecordPayload<'a>
 }
 
 impl<'a> Record<'a> {
-    pub fn new<L>(
-        labels: L,
+    pub fn new(
+        name: Name<'a>,
         class: Class,
         ttl: u32,
-        payload: RecordPayload
-    ) -> Self
-    where L: IntoIterator,
-          L::Item: Into<Cow<'a, str>>
-    {
-        let labels = labels.into_iter()
-            .map(|l| l.into())
-            .collect();
-        Record { labels, class, ttl, payload }
+        payload: RecordPayload<'a>
+    ) -> Self {
+        Record { name, class, ttl, payload }
+    }
+
+    /// Append the record to a message in `buffer`, compressing its
+    /// owner name and the names within its RDATA.
+    pub(crate) fn encode(&self, buffer: &mut Vec<u8>, encoded_labels: &mut HashMap<Name<'a>, usize>) -> Result<'static, ()> {
+        let pos = buffer.len();
+        buffer.extend(encode_labels(encoded_labels, pos, &self.name));
+        append_u16(buffer, u16::from(self.payload.typ()));
+        append_u16(buffer, u16::from(self.class));
+        append_u32(buffer, self.ttl);
+
+        let mut len_pos = buffer.len();
+        append_u16(buffer, 0);
+        self.payload.encode(buffer, Some(encoded_labels))?;
+        let len = buffer.len() - len_pos - 2;
+        if len > u16::MAX as usize {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("RDATA of {} bytes exceeds the maximum length", len)));
+        }
+        write_u16(buffer, &mut len_pos, len as u16);
+        Ok(())
+    }
+
+    pub fn into_owned(self) -> Record<'static> {
+        Record {
+            name: self.name.into_owned(),
+            class: self.class,
+            ttl: self.ttl,
+            payload: self.payload.into_owned()
+        }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
-pub enum RecordPayload {
+impl<'a> fmt::Display for Record<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {} {}", self.name, self.ttl, self.class, self.payload.typ(), self.payload)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub enum RecordPayload<'a> {
     A(Ipv4Addr),
+    NS(Name<'a>),
+    MD(Name<'a>),
+    MF(Name<'a>),
+    CNAME(Name<'a>),
+    SOA(Soa<'a>),
+    MB(Name<'a>),
+    MG(Name<'a>),
+    MR(Name<'a>),
+    NULL(Cow<'a, [u8]>),
+    WKS(Wks<'a>),
+    PTR(Name<'a>),
+    HINFO(Hinfo<'a>),
+    MINFO(Minfo<'a>),
+    MX(Mx<'a>),
+    TXT(#[serde(serialize_with = "serialize_character_strings")] Vec<Cow<'a, [u8]>>),
+    AAAA(Ipv6Addr),
+    SRV(Srv<'a>),
+    SVCB(Svcb<'a>),
+    HTTPS(Svcb<'a>),
+    CAA(Caa<'a>),
+    /// A record of a type this library does not know about, holding
+    /// the numeric type and the raw RDATA (RFC 3597).
+    Unknown(u16, Cow<'a, [u8]>),
+}
+
+impl<'a> RecordPayload<'a> {
+    pub fn into_owned(self) -> RecordPayload<'static> {
+        match self {
+            RecordPayload::A(ip) => RecordPayload::A(ip),
+            RecordPayload::NS(name) => RecordPayload::NS(name.into_owned()),
+            RecordPayload::MD(name) => RecordPayload::MD(name.into_owned()),
+            RecordPayload::MF(name) => RecordPayload::MF(name.into_owned()),
+            RecordPayload::CNAME(name) => RecordPayload::CNAME(name.into_owned()),
+            RecordPayload::SOA(soa) => RecordPayload::SOA(Soa {
+                mname: soa.mname.into_owned(),
+                rname: soa.rname.into_owned(),
+                serial: soa.serial,
+                refresh: soa.refresh,
+                retry: soa.retry,
+                expire: soa.expire,
+                minimum: soa.minimum
+            }),
+            RecordPayload::MB(name) => RecordPayload::MB(name.into_owned()),
+            RecordPayload::MG(name) => RecordPayload::MG(name.into_owned()),
+            RecordPayload::MR(name) => RecordPayload::MR(name.into_owned()),
+            RecordPayload::NULL(data) => RecordPayload::NULL(Cow::Owned(data.into_owned())),
+            RecordPayload::WKS(wks) => RecordPayload::WKS(Wks {
+                address: wks.address,
+                protocol: wks.protocol,
+                bitmap: Cow::Owned(wks.bitmap.into_owned())
+            }),
+            RecordPayload::PTR(name) => RecordPayload::PTR(name.into_owned()),
+            RecordPayload::HINFO(hinfo) => RecordPayload::HINFO(Hinfo {
+                cpu: Cow::Owned(hinfo.cpu.into_owned()),
+                os: Cow::Owned(hinfo.os.into_owned())
+            }),
+            RecordPayload::MINFO(minfo) => RecordPayload::MINFO(Minfo {
+                rmailbx: minfo.rmailbx.into_owned(),
+                emailbx: minfo.emailbx.into_owned()
+            }),
+            RecordPayload::MX(mx) => RecordPayload::MX(Mx {
+                preference: mx.preference,
+                exchange: mx.exchange.into_owned()
+            }),
+            RecordPayload::TXT(strings) => RecordPayload::TXT(
+                strings.into_iter().map(|s| Cow::Owned(s.into_owned())).collect()
+            ),
+            RecordPayload::AAAA(ip) => RecordPayload::AAAA(ip),
+            RecordPayload::SRV(srv) => RecordPayload::SRV(Srv {
+                priority: srv.priority,
+                weight: srv.weight,
+                port: srv.port,
+                target: srv.target.into_owned()
+            }),
+            RecordPayload::SVCB(svcb) => RecordPayload::SVCB(svcb.into_owned()),
+            RecordPayload::HTTPS(svcb) => RecordPayload::HTTPS(svcb.into_owned()),
+            RecordPayload::CAA(caa) => RecordPayload::CAA(Caa {
+                flags: caa.flags,
+                tag: Cow::Owned(caa.tag.into_owned()),
+                value: Cow::Owned(caa.value.into_owned())
+            }),
+            RecordPayload::Unknown(typ, data) => RecordPayload::Unknown(typ, Cow::Owned(data.into_owned())),
+        }
+    }
+
+    pub fn typ(&self) -> Type {
+        match *self {
+            RecordPayload::A(_) => Type::A,
+            RecordPayload::NS(_) => Type::NS,
+            RecordPayload::MD(_) => Type::MD,
+            RecordPayload::MF(_) => Type::MF,
+            RecordPayload::CNAME(_) => Type::CNAME,
+            RecordPayload::SOA(_) => Type::SOA,
+            RecordPayload::MB(_) => Type::MB,
+            RecordPayload::MG(_) => Type::MG,
+            RecordPayload::MR(_) => Type::MR,
+            RecordPayload::NULL(_) => Type::NULL,
+            RecordPayload::WKS(_) => Type::WKS,
+            RecordPayload::PTR(_) => Type::PTR,
+            RecordPayload::HINFO(_) => Type::HINFO,
+            RecordPayload::MINFO(_) => Type::MINFO,
+            RecordPayload::MX(_) => Type::MX,
+            RecordPayload::TXT(_) => Type::TXT,
+            RecordPayload::AAAA(_) => Type::AAAA,
+            RecordPayload::SRV(_) => Type::SRV,
+            RecordPayload::SVCB(_) => Type::SVCB,
+            RecordPayload::HTTPS(_) => Type::HTTPS,
+            RecordPayload::CAA(_) => Type::CAA,
+            RecordPayload::Unknown(typ, _) => Type::from(typ),
+        }
+    }
+
+    /// Encode the payload as RDATA in wire format. Domain names
+    /// within the RDATA are written uncompressed.
+    pub fn to_bytes(&self) -> Result<'static, Vec<u8>> {
+        let mut buffer = vec![];
+        self.encode(&mut buffer, None)?;
+        Ok(buffer)
+    }
+
+    /// Append the RDATA to a message in `buffer`. If `encoded_labels`
+    /// is given, names of the types defined in RFC 1035 are compressed
+    /// against the names already written to the message.
+    pub(crate) fn encode(
+        &self,
+        buffer: &mut Vec<u8>,
+        mut encoded_labels: Option<&mut HashMap<Name<'a>, usize>>
+    ) -> Result<'static, ()> {
+        match *self {
+            RecordPayload::A(ref ip) => buffer.extend(&ip.octets()),
+            RecordPayload::NS(ref name)
+            | RecordPayload::MD(ref name)
+            | RecordPayload::MF(ref name)
+            | RecordPayload::CNAME(ref name)
+            | RecordPayload::MB(ref name)
+            | RecordPayload::MG(ref name)
+            | RecordPayload::MR(ref name)
+            | RecordPayload::PTR(ref name) => append_name(buffer, &mut encoded_labels, name),
+            RecordPayload::SOA(ref soa) => {
+                append_name(buffer, &mut encoded_labels, &soa.mname);
+                append_name(buffer, &mut encoded_labels, &soa.rname);
+                append_u32(buffer, soa.serial);
+                append_u32(buffer, soa.refresh);
+                append_u32(buffer, soa.retry);
+                append_u32(buffer, soa.expire);
+                append_u32(buffer, soa.minimum);
+            },
+            RecordPayload::NULL(ref data) => buffer.extend(data.iter()),
+            RecordPayload::WKS(ref wks) => {
+                buffer.extend(&wks.address.octets());
+                buffer.push(wks.protocol);
+                buffer.extend(wks.bitmap.iter());
+            },
+            RecordPayload::HINFO(ref hinfo) => {
+                append_character_string(buffer, &hinfo.cpu)?;
+                append_character_string(buffer, &hinfo.os)?;
+            },
+            RecordPayload::MINFO(ref minfo) => {
+                append_name(buffer, &mut encoded_labels, &minfo.rmailbx);
+                append_name(buffer, &mut encoded_labels, &minfo.emailbx);
+            },
+            RecordPayload::MX(ref mx) => {
+                append_u16(buffer, mx.preference);
+                append_name(buffer, &mut encoded_labels, &mx.exchange);
+            },
+            RecordPayload::TXT(ref strings) => {
+                for string in strings {
+                    append_character_string(buffer, string)?;
+                }
+            },
+            RecordPayload::AAAA(ref ip) => buffer.extend(&ip.octets()),
+            // RFC 2782 forbids compressing the target
+            RecordPayload::SRV(ref srv) => {
+                append_u16(buffer, srv.priority);
+                append_u16(buffer, srv.weight);
+                append_u16(buffer, srv.port);
+                append_name(buffer, &mut None, &srv.target);
+            },
+            // as are SVCB targets (RFC 9460)
+            RecordPayload::SVCB(ref svcb) | RecordPayload::HTTPS(ref svcb) => {
+                append_u16(buffer, svcb.priority);
+                append_name(buffer, &mut None, &svcb.target);
+                for param in &svcb.params {
+                    param.encode(buffer)?;
+                }
+            },
+            RecordPayload::CAA(ref caa) => {
+                let tag = caa.tag.as_bytes();
+                if tag.is_empty() || tag.len() > MAX_CHARACTER_STRING_LEN || !tag.iter().all(u8::is_ascii_alphanumeric) {
+                    return Err(Error::new(DnsMsgError::InvalidData, format!("Invalid CAA tag {:?}", caa.tag)));
+                }
+                buffer.push(caa.flags);
+                buffer.push(caa.tag.len() as u8);
+                buffer.extend(caa.tag.as_bytes());
+                buffer.extend(caa.value.iter());
+            },
+            RecordPayload::Unknown(_, ref data) => buffer.extend(data.iter()),
+        }
+        Ok(())
+    }
+}
+
+impl RecordPayload<'static> {
+    /// Parse RDATA given in the generic `\# <length> <hex>` presentation
+    /// form (RFC 3597). The result is always an `Unknown` payload, even
+    /// if `typ` is a type known to this library.
+    pub fn from_generic(typ: Type, text: &str) -> Result<'static, Self> {
+        let mut parts = text.split_whitespace();
+        if parts.next() != Some("\\#") {
+            return Err(Error::new(DnsMsgError::InvalidData, "Generic RDATA has to start with \\#"));
+        }
+
+        let len = parts.next()
+            .and_then(|len| len.parse::<usize>().ok())
+            .ok_or(Error::new(DnsMsgError::InvalidData, "Missing length of generic RDATA"))?;
+        let hex: String = parts.collect();
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) || hex.len() != len * 2 {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("Generic RDATA does not contain {} hex encoded bytes", len)
+            ));
+        }
+
+        let bytes = (0..len)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+            .collect::<Vec<u8>>();
+        Ok(RecordPayload::Unknown(u16::from(typ), Cow::from(bytes)))
+    }
+}
+
+impl<'a> fmt::Display for RecordPayload<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecordPayload::A(ref ip) => write!(f, "{}", ip),
+            RecordPayload::NS(ref name)
+            | RecordPayload::MD(ref name)
+            | RecordPayload::MF(ref name)
+            | RecordPayload::CNAME(ref name)
+            | RecordPayload::MB(ref name)
+            | RecordPayload::MG(ref name)
+            | RecordPayload::MR(ref name)
+            | RecordPayload::PTR(ref name) => write!(f, "{}", name),
+            RecordPayload::SOA(ref soa) => {
+                write!(f, "{} {} {} {} {} {} {}", soa.mname, soa.rname, soa.serial, soa.refresh, soa.retry, soa.expire, soa.minimum)
+            },
+            RecordPayload::NULL(ref data) => write_generic(f, data),
+            RecordPayload::WKS(ref wks) => {
+                write!(f, "{} {}", wks.address, wks.protocol)?;
+                for (i, byte) in wks.bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            },
+            RecordPayload::HINFO(ref hinfo) => {
+                write_character_string(f, &hinfo.cpu)?;
+                f.write_str(" ")?;
+                write_character_string(f, &hinfo.os)
+            },
+            RecordPayload::MINFO(ref minfo) => write!(f, "{} {}", minfo.rmailbx, minfo.emailbx),
+            RecordPayload::MX(ref mx) => write!(f, "{} {}", mx.preference, mx.exchange),
+            RecordPayload::TXT(ref strings) => {
+                for (i, string) in strings.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write_character_string(f, string)?;
+                }
+                Ok(())
+            },
+            RecordPayload::AAAA(ref ip) => write!(f, "{}", ip),
+            RecordPayload::SRV(ref srv) => write!(f, "{} {} {} {}", srv.priority, srv.weight, srv.port, srv.target),
+            RecordPayload::SVCB(ref svcb) | RecordPayload::HTTPS(ref svcb) => write!(f, "{}", svcb),
+            RecordPayload::CAA(ref caa) => {
+                write!(f, "{} {} ", caa.flags, caa.tag)?;
+                write_character_string(f, &caa.value)
+            },
+            RecordPayload::Unknown(_, ref data) => write_generic(f, data),
+        }
+    }
+}
+
+/// The contents of a character-string in presentation format. As
+/// character-strings hold arbitrary octets, quotes, backslashes and
+/// non-printable octets are escaped.
+struct Escaped<'r>(&'r [u8]);
+
+impl<'r> fmt::Display for Escaped<'r> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &byte in self.0 {
+            match byte {
+                b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+                0x20..=0x7e => write!(f, "{}", byte as char)?,
+                _ => write!(f, "\\{:03}", byte)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'r> Serialize for Escaped<'r> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+fn serialize_character_string<S: Serializer>(string: &[u8], serializer: S) -> result::Result<S::Ok, S::Error> {
+    Escaped(string).serialize(serializer)
+}
+
+fn serialize_character_strings<S: Serializer>(strings: &[Cow<[u8]>], serializer: S) -> result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(strings.iter().map(|string| Escaped(string)))
+}
+
+fn write_character_string(f: &mut fmt::Formatter, string: &[u8]) -> fmt::Result {
+    write!(f, "\"{}\"", Escaped(string))
+}
+
+fn write_generic(f: &mut fmt::Formatter, data: &[u8]) -> fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        f.write_str(" ")?;
+        for byte in data {
+            write!(f, "{:02X}", byte)?;
+        }
+    }
+    Ok(())
+}
+
+fn append_name<'a>(
+    buffer: &mut Vec<u8>,
+    encoded_labels: &mut Option<&mut HashMap<Name<'a>, usize>>,
+    name: &Name<'a>
+) {
+    match *encoded_labels {
+        Some(ref mut encoded_labels) => {
+            let pos = buffer.len();
+            buffer.extend(encode_labels(encoded_labels, pos, name));
+        },
+        None => {
+            for label in name.labels() {
+                buffer.push(label.len() as u8);
+                buffer.extend(label);
+            }
+            buffer.push(0);
+        }
+    }
+}
+
+fn append_character_string(buffer: &mut Vec<u8>, string: &[u8]) -> Result<'static, ()> {
+    if string.len() > MAX_CHARACTER_STRING_LEN {
+        return Err(Error::new(
+            DnsMsgError::InvalidData,
+            format!("Character string of {} bytes exceeds the maximum length of {}", string.len(), MAX_CHARACTER_STRING_LEN)
+        ));
+    }
+    buffer.push(string.len() as u8);
+    buffer.extend(string);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Soa<'a> {
+    pub mname: Name<'a>,
+    pub rname: Name<'a>,
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Wks<'a> {
+    pub address: Ipv4Addr,
+    pub protocol: u8,
+    pub bitmap: Cow<'a, [u8]>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Hinfo<'a> {
+    #[serde(serialize_with = "serialize_character_string")]
+    pub cpu: Cow<'a, [u8]>,
+    #[serde(serialize_with = "serialize_character_string")]
+    pub os: Cow<'a, [u8]>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Minfo<'a> {
+    pub rmailbx: Name<'a>,
+    pub emailbx: Name<'a>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Mx<'a> {
+    pub preference: u16,
+    pub exchange: Name<'a>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Srv<'a> {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// The host providing the service, or the root if the service is
+    /// not available at the domain.
+    pub target: Name<'a>
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Caa<'a> {
+    pub flags: u8,
+    /// The property, such as `issue`, `issuewild` or `iodef`, made of
+    /// ASCII letters and digits.
+    pub tag: Cow<'a, str>,
+    pub value: Cow<'a, [u8]>
+}
+
+#[cfg(test)]
+mod test {
+    extern crate serde_json;
+
+    use super::*;
+    use ::parse;
+
+    fn round_trip(payload: RecordPayload) {
+        let mut buffer = vec![0u8; 12];
+        write_u16(&mut buffer, &mut 6, 1);
+        buffer.push(0); // root label
+        append_u16(&mut buffer, u16::from(payload.typ()));
+        append_u16(&mut buffer, u16::from(Class::IN));
+        append_u32(&mut buffer, 300);
+        let rdata = payload.to_bytes().unwrap();
+        append_u16(&mut buffer, rdata.len() as u16);
+        buffer.extend(&rdata);
+
+        let result = parse(&buffer).unwrap();
+        assert_eq!(&payload, &result.answers()[0].payload);
+    }
+
+    fn name(address: &str) -> Name<'static> {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn should_encode_address_payloads() {
+        round_trip(RecordPayload::A(Ipv4Addr::new(127, 0, 0, 1)));
+        round_trip(RecordPayload::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+        round_trip(RecordPayload::WKS(Wks {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            protocol: 6,
+            bitmap: Cow::from(vec![0, 0, 0x40])
+        }));
+    }
+
+    #[test]
+    fn should_encode_name_payloads() {
+        round_trip(RecordPayload::NS(name("ns1.google.com")));
+        round_trip(RecordPayload::CNAME(name("www.google.com")));
+        round_trip(RecordPayload::PTR(name("host.example.org")));
+        round_trip(RecordPayload::MX(Mx { preference: 10, exchange: name("smtp.google.com") }));
+        round_trip(RecordPayload::SRV(Srv { priority: 10, weight: 60, port: 5060, target: name("sip.example.com") }));
+        round_trip(RecordPayload::HTTPS("1 . alpn=h2 ipv4hint=10.0.0.1".parse().unwrap()));
+        round_trip(RecordPayload::MINFO(Minfo {
+            rmailbx: name("admin.google.com"),
+            emailbx: name("errors.google.com")
+        }));
+        round_trip(RecordPayload::SOA(Soa {
+            mname: name("ns1.google.com"),
+            rname: name("dns-admin.google.com"),
+            serial: 1,
+            refresh: 2,
+            retry: 3,
+            expire: 4,
+            minimum: 5
+        }));
+    }
+
+    #[test]
+    fn should_encode_string_payloads() {
+        round_trip(RecordPayload::TXT(vec![Cow::from(&b"v=spf1 -all"[..]), Cow::from(&b""[..]), Cow::from(&b"\xff\x00"[..])]));
+        round_trip(RecordPayload::HINFO(Hinfo { cpu: Cow::from(&b"x86"[..]), os: Cow::from(&b"Linux"[..]) }));
+        round_trip(RecordPayload::NULL(Cow::from(vec![0xde, 0xad, 0xbe, 0xef])));
+        round_trip(RecordPayload::CAA(Caa { flags: 128, tag: Cow::from("issue"), value: Cow::from(&b"ca.example.net"[..]) }));
+    }
+
+    #[test]
+    fn should_reject_invalid_caa_tags() {
+        let long = "a".repeat(256);
+        let caa = |tag| RecordPayload::CAA(Caa { flags: 0, tag: Cow::from(tag), value: Cow::from(&b"ca.example.net"[..]) });
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid CAA tag \"\"")), caa("").to_bytes());
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, format!("Invalid CAA tag {:?}", long))), caa(&long).to_bytes());
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, "Invalid CAA tag \"issue-wild\"")), caa("issue-wild").to_bytes());
+        round_trip(caa(&long[..255]));
+    }
+
+    #[test]
+    fn should_reject_long_character_strings() {
+        let long = vec![b'a'; 300];
+        let txt = RecordPayload::TXT(vec![Cow::from(&b"v=spf1"[..]), Cow::from(&long[..])]);
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Character string of 300 bytes exceeds the maximum length of 255")),
+            txt.to_bytes()
+        );
+        round_trip(RecordPayload::TXT(vec![Cow::from(&long[..255])]));
+
+        let hinfo = RecordPayload::HINFO(Hinfo { cpu: Cow::from(&long[..256]), os: Cow::from(&b"Linux"[..]) });
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Character string of 256 bytes exceeds the maximum length of 255")),
+            hinfo.to_bytes()
+        );
+    }
+
+    #[test]
+    fn should_encode_unknown_payloads() {
+        round_trip(RecordPayload::Unknown(1234, Cow::from(vec![1, 2, 3])));
+        round_trip(RecordPayload::Unknown(4321, Cow::from(vec![])));
+    }
+
+    #[test]
+    fn should_present_unknown_payloads_in_generic_form() {
+        let payload = RecordPayload::Unknown(1234, Cow::from(vec![0x0a, 0, 0, 1]));
+        assert_eq!("\\# 4 0A000001", payload.to_string());
+        assert_eq!("\\# 0", RecordPayload::Unknown(1234, Cow::from(vec![])).to_string());
+
+        let record = Record::new(name("example.com"), Class::Unknown(32), 300, payload);
+        assert_eq!("example.com. 300 CLASS32 TYPE1234 \\# 4 0A000001", record.to_string());
+    }
+
+    #[test]
+    fn should_parse_generic_form() {
+        assert_eq!(
+            Ok(RecordPayload::Unknown(1234, Cow::from(vec![0x0a, 0, 0, 1]))),
+            RecordPayload::from_generic(Type::Unknown(1234), "\\# 4 0A 000001")
+        );
+        assert_eq!(
+            Ok(RecordPayload::Unknown(1234, Cow::from(vec![]))),
+            RecordPayload::from_generic(Type::Unknown(1234), "\\# 0")
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Generic RDATA does not contain 4 hex encoded bytes")),
+            RecordPayload::from_generic(Type::Unknown(1234), "\\# 4 0A0000")
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Generic RDATA has to start with \\#")),
+            RecordPayload::from_generic(Type::A, "10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn should_present_known_payloads() {
+        assert_eq!("10 smtp.google.com.", RecordPayload::MX(Mx { preference: 10, exchange: name("smtp.google.com") }).to_string());
+        assert_eq!("0 5 5060 sip.example.com.", RecordPayload::SRV(Srv { priority: 0, weight: 5, port: 5060, target: name("sip.example.com") }).to_string());
+        assert_eq!("0 issue \"ca.example.net; account=230123\"", RecordPayload::CAA(Caa {
+            flags: 0,
+            tag: Cow::from("issue"),
+            value: Cow::from(&b"ca.example.net; account=230123"[..])
+        }).to_string());
+        assert_eq!(
+            "\"a \\\"b\\\"\" \"\\009\\255\"",
+            RecordPayload::TXT(vec![Cow::from(&b"a \"b\""[..]), Cow::from(&b"\t\xff"[..])]).to_string()
+        );
+        assert_eq!("\"x86\" \"Linux\"", RecordPayload::HINFO(Hinfo { cpu: Cow::from(&b"x86"[..]), os: Cow::from(&b"Linux"[..]) }).to_string());
+        assert_eq!("10.0.0.1 6 25 80", RecordPayload::WKS(Wks {
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            protocol: 6,
+            bitmap: Cow::from(vec![0, 0, 0, 0b_0100_0000, 0, 0, 0, 0, 0, 0, 0b_1000_0000])
+        }).to_string());
+    }
+
+    #[test]
+    fn should_serialize_character_strings_escaped() {
+        let txt = RecordPayload::TXT(vec![Cow::from(&b"v=spf1 -all"[..]), Cow::from(&b"\"\xff"[..])]);
+        assert_eq!(r#"{"TXT":["v=spf1 -all","\\\"\\255"]}"#, serde_json::to_string(&txt).unwrap());
+
+        let hinfo = RecordPayload::HINFO(Hinfo { cpu: Cow::from(&b"x86"[..]), os: Cow::from(&b"\tLinux"[..]) });
+        assert_eq!(r#"{"HINFO":{"cpu":"x86","os":"\\009Linux"}}"#, serde_json::to_string(&hinfo).unwrap());
+    }
 }
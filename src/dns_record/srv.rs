@@ -0,0 +1,81 @@
+use rand::Rng;
+use ::dns_record::records::Srv;
+
+/// Order SRV records for contacting their targets (RFC 2782): by
+/// priority, and within a priority at random, with each record's chance
+/// of coming next proportional to its weight. Records with weight 0
+/// only have a small chance of being picked before others.
+pub fn order_targets<'r, 'a, R: Rng>(mut records: Vec<&'r Srv<'a>>, rng: &mut R) -> Vec<&'r Srv<'a>> {
+    records.sort_by_key(|srv| srv.priority);
+
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let end = records.iter().position(|srv| srv.priority != priority).unwrap_or(records.len());
+        let mut group: Vec<_> = records.drain(..end).collect();
+        // the weight 0 records go first, so they are only picked when
+        // the random number is 0
+        group.sort_by_key(|srv| srv.weight != 0);
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|srv| u32::from(srv.weight)).sum();
+            let pick = rng.gen_range(0, total + 1);
+            let mut sum = 0;
+            let index = group.iter()
+                .position(|srv| {
+                    sum += u32::from(srv.weight);
+                    sum >= pick
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{SeedableRng, XorShiftRng};
+    use ::name::Name;
+
+    fn srv(priority: u16, weight: u16, target: &str) -> Srv<'static> {
+        Srv { priority, weight, port: 443, target: target.parse::<Name>().unwrap() }
+    }
+
+    fn targets(ordered: Vec<&Srv>) -> Vec<String> {
+        ordered.iter().map(|srv| srv.target.to_string()).collect()
+    }
+
+    #[test]
+    fn should_order_by_priority() {
+        let records = [srv(20, 0, "c.example."), srv(10, 0, "a.example."), srv(10, 5, "b.example.")];
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        for _ in 0..20 {
+            let ordered = targets(order_targets(records.iter().collect(), &mut rng));
+            assert_eq!(3, ordered.len());
+            assert_eq!("c.example.", ordered[2]);
+        }
+    }
+
+    #[test]
+    fn should_pick_by_weight() {
+        let records = [srv(10, 90, "heavy.example."), srv(10, 10, "light.example."), srv(10, 0, "zero.example.")];
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        let mut heavy_first = 0;
+        let mut zero_first = 0;
+        for _ in 0..1000 {
+            let ordered = targets(order_targets(records.iter().collect(), &mut rng));
+            match ordered[0].as_str() {
+                "heavy.example." => heavy_first += 1,
+                "zero.example." => zero_first += 1,
+                _ => {},
+            }
+        }
+        assert!(heavy_first > 850 && heavy_first < 950, "{}", heavy_first);
+        assert!(zero_first < 30, "{}", zero_first);
+    }
+}
@@ -0,0 +1,600 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::{self, FromStr};
+use ::enums::SvcParamKey;
+use ::errors::*;
+use ::name::{self, Name};
+use ::utils::{append_u16, write_u16};
+
+/// The RDATA of SVCB and HTTPS records (RFC 9460). A priority of 0
+/// makes the record an alias for `target`; otherwise it describes an
+/// endpoint of the service with the parameters in `params`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Svcb<'a> {
+    pub priority: u16,
+    /// The endpoint, or the root for the owner name of the record.
+    pub target: Name<'a>,
+    /// The parameters, in strictly increasing order of their keys.
+    pub params: Vec<SvcParam<'a>>
+}
+
+impl<'a> Svcb<'a> {
+    /// Create the RDATA, putting `params` and the keys listed as
+    /// mandatory into key order and checking them as `validate` does.
+    pub fn new(priority: u16, target: Name<'a>, mut params: Vec<SvcParam<'a>>) -> Result<'static, Self> {
+        params.sort_by_key(|param| u16::from(param.key()));
+        for param in &mut params {
+            if let SvcParam::Mandatory(ref mut keys) = *param {
+                keys.sort_by_key(|&key| u16::from(key));
+            }
+        }
+        let svcb = Svcb { priority, target, params };
+        svcb.validate()?;
+        Ok(svcb)
+    }
+
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+
+    pub fn param(&self, key: SvcParamKey) -> Option<&SvcParam<'a>> {
+        self.params.iter().find(|param| param.key() == key)
+    }
+
+    /// Check that the keys are in strictly increasing order, that every
+    /// value is well-formed, that the keys listed as mandatory are
+    /// present and don't include `mandatory` itself, and that
+    /// `no-default-alpn` comes with `alpn` (RFC 9460, section 7.1.1).
+    pub fn validate(&self) -> Result<'static, ()> {
+        for pair in self.params.windows(2) {
+            let (previous, key) = (pair[0].key(), pair[1].key());
+            if u16::from(previous) == u16::from(key) {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("SvcParam {} is given more than once", key)));
+            }
+            if u16::from(previous) > u16::from(key) {
+                return Err(Error::new(DnsMsgError::InvalidData, format!("SvcParam {} has to come before {}", key, previous)));
+            }
+        }
+
+        for param in &self.params {
+            param.validate()?;
+        }
+
+        if let Some(SvcParam::Mandatory(keys)) = self.param(SvcParamKey::Mandatory) {
+            for (i, &key) in keys.iter().enumerate() {
+                if key == SvcParamKey::Mandatory {
+                    return Err(Error::new(DnsMsgError::InvalidData, "SvcParam mandatory must not list itself"));
+                }
+                if i > 0 && u16::from(keys[i - 1]) >= u16::from(key) {
+                    return Err(Error::new(DnsMsgError::InvalidData, "Keys in mandatory have to be in strictly increasing order"));
+                }
+                if self.param(key).is_none() {
+                    return Err(Error::new(DnsMsgError::InvalidData, format!("Mandatory SvcParam {} is missing", key)));
+                }
+            }
+        }
+
+        if self.param(SvcParamKey::NoDefaultAlpn).is_some() && self.param(SvcParamKey::Alpn).is_none() {
+            return Err(Error::new(DnsMsgError::InvalidData, "SvcParam no-default-alpn requires alpn"));
+        }
+
+        Ok(())
+    }
+
+    pub fn into_owned(self) -> Svcb<'static> {
+        Svcb {
+            priority: self.priority,
+            target: self.target.into_owned(),
+            params: self.params.into_iter().map(SvcParam::into_owned).collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub enum SvcParam<'a> {
+    Mandatory(Vec<SvcParamKey>),
+    Alpn(Vec<Cow<'a, [u8]>>),
+    NoDefaultAlpn,
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An encoded ECHConfigList.
+    Ech(Cow<'a, [u8]>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// The URI template of a DNS over HTTPS endpoint (RFC 9461).
+    DohPath(Cow<'a, str>),
+    /// A parameter of a key without a typed variant, with its raw value.
+    Unknown(u16, Cow<'a, [u8]>),
+}
+
+impl<'a> SvcParam<'a> {
+    pub fn key(&self) -> SvcParamKey {
+        match *self {
+            SvcParam::Mandatory(_) => SvcParamKey::Mandatory,
+            SvcParam::Alpn(_) => SvcParamKey::Alpn,
+            SvcParam::NoDefaultAlpn => SvcParamKey::NoDefaultAlpn,
+            SvcParam::Port(_) => SvcParamKey::Port,
+            SvcParam::Ipv4Hint(_) => SvcParamKey::Ipv4Hint,
+            SvcParam::Ech(_) => SvcParamKey::Ech,
+            SvcParam::Ipv6Hint(_) => SvcParamKey::Ipv6Hint,
+            SvcParam::DohPath(_) => SvcParamKey::DohPath,
+            SvcParam::Unknown(key, _) => SvcParamKey::from(key),
+        }
+    }
+
+    fn validate(&self) -> Result<'static, ()> {
+        let valid = match *self {
+            SvcParam::Mandatory(ref keys) => !keys.is_empty(),
+            SvcParam::Alpn(ref ids) => !ids.is_empty() && ids.iter().all(|id| !id.is_empty() && id.len() <= 255),
+            SvcParam::Ipv4Hint(ref addresses) => !addresses.is_empty(),
+            SvcParam::Ipv6Hint(ref addresses) => !addresses.is_empty(),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("Invalid value for SvcParam {}", self.key())));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn encode(&self, buffer: &mut Vec<u8>) -> Result<'static, ()> {
+        // the fields are public, so values may not have been validated
+        self.validate()?;
+        append_u16(buffer, u16::from(self.key()));
+        let mut len_pos = buffer.len();
+        append_u16(buffer, 0);
+
+        match *self {
+            SvcParam::Mandatory(ref keys) => {
+                for &key in keys {
+                    append_u16(buffer, u16::from(key));
+                }
+            },
+            SvcParam::Alpn(ref ids) => {
+                for id in ids {
+                    buffer.push(id.len() as u8);
+                    buffer.extend(id.iter());
+                }
+            },
+            SvcParam::NoDefaultAlpn => {},
+            SvcParam::Port(port) => append_u16(buffer, port),
+            SvcParam::Ipv4Hint(ref addresses) => {
+                for address in addresses {
+                    buffer.extend(&address.octets());
+                }
+            },
+            SvcParam::Ipv6Hint(ref addresses) => {
+                for address in addresses {
+                    buffer.extend(&address.octets());
+                }
+            },
+            SvcParam::Ech(ref data) | SvcParam::Unknown(_, ref data) => buffer.extend(data.iter()),
+            SvcParam::DohPath(ref template) => buffer.extend(template.as_bytes()),
+        }
+
+        let len = buffer.len() - len_pos - 2;
+        if len > u16::MAX as usize {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("Value of SvcParam {} exceeds the maximum length", self.key())
+            ));
+        }
+        write_u16(buffer, &mut len_pos, len as u16);
+        Ok(())
+    }
+
+    pub fn into_owned(self) -> SvcParam<'static> {
+        match self {
+            SvcParam::Mandatory(keys) => SvcParam::Mandatory(keys),
+            SvcParam::Alpn(ids) => SvcParam::Alpn(ids.into_iter().map(|id| Cow::Owned(id.into_owned())).collect()),
+            SvcParam::NoDefaultAlpn => SvcParam::NoDefaultAlpn,
+            SvcParam::Port(port) => SvcParam::Port(port),
+            SvcParam::Ipv4Hint(addresses) => SvcParam::Ipv4Hint(addresses),
+            SvcParam::Ech(data) => SvcParam::Ech(Cow::Owned(data.into_owned())),
+            SvcParam::Ipv6Hint(addresses) => SvcParam::Ipv6Hint(addresses),
+            SvcParam::DohPath(template) => SvcParam::DohPath(Cow::Owned(template.into_owned())),
+            SvcParam::Unknown(key, data) => SvcParam::Unknown(key, Cow::Owned(data.into_owned())),
+        }
+    }
+
+    /// Parse the presentation form of a value, which has already been
+    /// unquoted and unescaped.
+    fn from_value(key: SvcParamKey, value: Option<Vec<u8>>) -> Result<'static, SvcParam<'static>> {
+        let value = match (key, value) {
+            (SvcParamKey::NoDefaultAlpn, None) => return Ok(SvcParam::NoDefaultAlpn),
+            (SvcParamKey::NoDefaultAlpn, Some(_)) => {
+                return Err(Error::new(DnsMsgError::InvalidData, "SvcParam no-default-alpn must not have a value"));
+            },
+            (SvcParamKey::Unknown(key), value) => return Ok(SvcParam::Unknown(key, Cow::from(value.unwrap_or_default()))),
+            (_, Some(value)) => value,
+            (_, None) => return Err(Error::new(DnsMsgError::InvalidData, format!("SvcParam {} needs a value", key))),
+        };
+        let invalid = || Error::new(DnsMsgError::InvalidData, format!("Invalid value for SvcParam {}", key));
+        let text = || String::from_utf8(value.clone()).map_err(|_| invalid());
+
+        let param = match key {
+            SvcParamKey::Mandatory => SvcParam::Mandatory(
+                split_list(&value).iter()
+                    .map(|key| str::from_utf8(key).ok().and_then(|key| key.parse().ok()).ok_or_else(invalid))
+                    .collect::<Result<_>>()?
+            ),
+            SvcParamKey::Alpn => SvcParam::Alpn(split_list(&value).into_iter().map(Cow::from).collect()),
+            SvcParamKey::Port => SvcParam::Port(text()?.parse().map_err(|_| invalid())?),
+            SvcParamKey::Ipv4Hint => SvcParam::Ipv4Hint(
+                text()?.split(',').map(|address| address.parse().map_err(|_| invalid())).collect::<Result<_>>()?
+            ),
+            SvcParamKey::Ech => SvcParam::Ech(Cow::from(base64_decode(&value).ok_or_else(invalid)?)),
+            SvcParamKey::Ipv6Hint => SvcParam::Ipv6Hint(
+                text()?.split(',').map(|address| address.parse().map_err(|_| invalid())).collect::<Result<_>>()?
+            ),
+            SvcParamKey::DohPath => SvcParam::DohPath(Cow::from(text()?)),
+            SvcParamKey::NoDefaultAlpn | SvcParamKey::Unknown(_) => unreachable!(),
+        };
+        Ok(param)
+    }
+}
+
+impl<'a> fmt::Display for Svcb<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        for param in &self.params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for SvcParam<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.key())?;
+        match *self {
+            SvcParam::NoDefaultAlpn => return Ok(()),
+            SvcParam::Unknown(_, ref data) if data.is_empty() => return Ok(()),
+            _ => f.write_str("=")?,
+        }
+
+        match *self {
+            SvcParam::Mandatory(ref keys) => write_list(f, keys),
+            SvcParam::Alpn(ref ids) => {
+                for (i, id) in ids.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    // commas and backslashes within an id are escaped
+                    // for the list first, then for the value
+                    let mut escaped = vec![];
+                    for &byte in id.iter() {
+                        if byte == b',' || byte == b'\\' {
+                            escaped.push(b'\\');
+                        }
+                        escaped.push(byte);
+                    }
+                    write_value(f, &escaped)?;
+                }
+                Ok(())
+            },
+            SvcParam::Port(port) => write!(f, "{}", port),
+            SvcParam::Ipv4Hint(ref addresses) => write_list(f, addresses),
+            SvcParam::Ech(ref data) => f.write_str(&base64_encode(data)),
+            SvcParam::Ipv6Hint(ref addresses) => write_list(f, addresses),
+            SvcParam::DohPath(ref template) => write_value(f, template.as_bytes()),
+            SvcParam::Unknown(_, ref data) => write_value(f, data),
+            SvcParam::NoDefaultAlpn => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Svcb<'static> {
+    type Err = Error<'static>;
+
+    /// Parse the presentation form `<priority> <target> <params>...`,
+    /// with parameters given as `key=value` and values optionally
+    /// quoted, e.g. `1 . alpn=h2,h3 port=8443`.
+    fn from_str(s: &str) -> Result<'static, Self> {
+        let tokens = tokenize(s)?;
+        if tokens.len() < 2 {
+            return Err(Error::new(DnsMsgError::InvalidData, "SVCB records need a priority and a target"));
+        }
+
+        let priority = tokens[0].parse::<u16>()
+            .map_err(|_| Error::new(DnsMsgError::InvalidData, format!("Invalid SVCB priority {}", tokens[0])))?;
+        let target = tokens[1].parse::<Name>()?;
+
+        let mut params = vec![];
+        for token in &tokens[2..] {
+            let mut parts = token.splitn(2, '=');
+            let key = parts.next().unwrap().parse::<SvcParamKey>()?;
+            let value = match parts.next() {
+                Some(value) if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') => {
+                    Some(unescape(&value[1..value.len() - 1])?)
+                },
+                Some(value) => Some(unescape(value)?),
+                None => None,
+            };
+            params.push(SvcParam::from_value(key, value)?);
+        }
+
+        Svcb::new(priority, target, params)
+    }
+}
+
+/// Split the presentation form at whitespace outside of quotes.
+fn tokenize(s: &str) -> Result<'static, Vec<&str>> {
+    let mut tokens = vec![];
+    let mut start = None;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if let Some(start) = start.take() {
+                    tokens.push(&s[start..i]);
+                }
+                continue;
+            },
+            _ => {},
+        }
+        start.get_or_insert(i);
+    }
+
+    if quoted {
+        return Err(Error::new(DnsMsgError::InvalidData, "SVCB records must not end within a quoted value"));
+    }
+    if let Some(start) = start {
+        tokens.push(&s[start..]);
+    }
+    Ok(tokens)
+}
+
+/// Resolve the `\X` and `\DDD` escape sequences of a value.
+fn unescape(value: &str) -> Result<'static, Vec<u8>> {
+    let bytes = value.as_bytes();
+    let mut unescaped = vec![];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            unescaped.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+
+        let (byte, len) = name::unescape(&bytes[i + 1..])?;
+        unescaped.push(byte);
+        i += 1 + len;
+    }
+
+    Ok(unescaped)
+}
+
+/// Split a comma-separated list, in which a backslash escapes the
+/// following comma or backslash (RFC 9460, Appendix A.1).
+fn split_list(value: &[u8]) -> Vec<Vec<u8>> {
+    let mut items = vec![vec![]];
+    let mut bytes = value.iter();
+
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => items.last_mut().unwrap().extend(bytes.next()),
+            b',' => items.push(vec![]),
+            _ => items.last_mut().unwrap().push(byte),
+        }
+    }
+
+    items
+}
+
+fn write_value(f: &mut fmt::Formatter, value: &[u8]) -> fmt::Result {
+    for &byte in value {
+        match byte {
+            b'"' | b'\\' | b'(' | b')' | b';' => write!(f, "\\{}", byte as char)?,
+            0x21..=0x7e => write!(f, "{}", byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn base64_decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    if !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (n, chunk) in encoded.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && n + 1 < encoded.len() / 4) {
+            return None;
+        }
+
+        let mut bits = 0u32;
+        for (i, &c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64.iter().position(|&b| b == c)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        decoded.extend(&[(bits >> 16) as u8, (bits >> 8) as u8, bits as u8][..3 - padding]);
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::dns_record::records::RecordPayload;
+
+    fn svcb(s: &str) -> Svcb<'static> {
+        s.parse().unwrap()
+    }
+
+    fn invalid(s: &str) -> Error<'static> {
+        s.parse::<Svcb>().unwrap_err()
+    }
+
+    fn wire(svcb: &Svcb) -> Vec<u8> {
+        RecordPayload::HTTPS(svcb.clone()).to_bytes().unwrap()
+    }
+
+    // test vectors from RFC 9460, Appendix D
+    #[test]
+    fn should_encode_alias_and_service_forms() {
+        let alias = svcb("0 foo.example.com.");
+        assert!(alias.is_alias());
+        assert_eq!(b"\x00\x00\x03foo\x07example\x03com\x00".to_vec(), wire(&alias));
+        assert_eq!(b"\x00\x01\x00".to_vec(), wire(&svcb("1 .")));
+        assert_eq!(
+            b"\x00\x10\x03foo\x07example\x03com\x00\x00\x03\x00\x02\x00\x35".to_vec(),
+            wire(&svcb("16 foo.example.com. port=53"))
+        );
+        assert_eq!(
+            b"\x00\x01\x03foo\x07example\x03com\x00\x02\x9b\x00\x09hello\xd2qoo".to_vec(),
+            wire(&svcb("1 foo.example.com. key667=\"hello\\210qoo\""))
+        );
+    }
+
+    #[test]
+    fn should_encode_params_in_key_order() {
+        let parsed = svcb("16 foo.example.org. alpn=h2,h3-19 mandatory=ipv4hint,alpn ipv4hint=192.0.2.1");
+        let mut expected = b"\x00\x10\x03foo\x07example\x03org\x00".to_vec();
+        expected.extend(b"\x00\x00\x00\x04\x00\x01\x00\x04");
+        expected.extend(b"\x00\x01\x00\x09\x02h2\x05h3-19");
+        expected.extend(b"\x00\x04\x00\x04\xc0\x00\x02\x01");
+        assert_eq!(expected, wire(&parsed));
+        assert_eq!(Some(&SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)])), parsed.param(SvcParamKey::Ipv4Hint));
+    }
+
+    #[test]
+    fn should_unescape_alpn_lists() {
+        let parsed = svcb("16 foo.example.org. alpn=\"f\\\\\\\\oo\\\\,bar,h2\"");
+        assert_eq!(
+            vec![SvcParam::Alpn(vec![Cow::from(&b"f\\oo,bar"[..]), Cow::from(&b"h2"[..])])],
+            parsed.params
+        );
+        assert_eq!("16 foo.example.org. alpn=f\\\\\\\\oo\\\\,bar,h2", parsed.to_string());
+        assert_eq!(parsed, svcb(&parsed.to_string()));
+    }
+
+    #[test]
+    fn should_present_all_params() {
+        let text = "1 svc.example. mandatory=alpn,port alpn=h2,h3 no-default-alpn port=8443 ipv4hint=192.0.2.1,192.0.2.2 \
+                    ech=AEP+DQA/ ipv6hint=2001:db8::1 dohpath=/dns-query{?dns} key65333";
+        let parsed = svcb(text);
+        assert_eq!(9, parsed.params.len());
+        assert_eq!(Some(&SvcParam::Ech(Cow::from(vec![0x00, 0x43, 0xfe, 0x0d, 0x00, 0x3f]))), parsed.param(SvcParamKey::Ech));
+        assert_eq!(text.split_whitespace().collect::<Vec<_>>().join(" "), parsed.to_string());
+        assert_eq!("1 . key1234=a\\032b", svcb("1 . key1234=\"a b\"").to_string());
+    }
+
+    #[test]
+    fn should_reject_invalid_params() {
+        assert_eq!(
+            Error::new(DnsMsgError::InvalidData, "SvcParam key123 is given more than once"),
+            invalid("1 foo.example.com. key123=abc key123=def")
+        );
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "SvcParam mandatory needs a value"), invalid("1 . mandatory"));
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "SvcParam alpn needs a value"), invalid("1 . alpn"));
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "Invalid value for SvcParam alpn"), invalid("1 . alpn=h2,,h3"));
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "Invalid value for SvcParam port"), invalid("1 . port=https"));
+        assert_eq!(
+            Error::new(DnsMsgError::InvalidData, "SvcParam no-default-alpn must not have a value"),
+            invalid("1 . alpn=h2 no-default-alpn=abc")
+        );
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "Invalid value for SvcParam ipv6hint"), invalid("1 . ipv6hint=1.2.3.4"));
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "Invalid value for SvcParam ech"), invalid("1 . ech=AEP"));
+        assert_eq!(
+            Error::new(DnsMsgError::InvalidData, "Escaped octets have to be given as three digits between 000 and 255"),
+            invalid("1 . key667=a\\25")
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_alpn_ids_when_encoding() {
+        let long = vec![b'a'; 256];
+        for ids in [vec![Cow::from(&b""[..])], vec![Cow::from(&b"h2"[..]), Cow::from(&long[..])]] {
+            let svcb = Svcb { priority: 1, target: Name::root(), params: vec![SvcParam::Alpn(ids)] };
+            assert_eq!(
+                Err(Error::new(DnsMsgError::InvalidData, "Invalid value for SvcParam alpn")),
+                RecordPayload::HTTPS(svcb).to_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn should_validate_mandatory_keys() {
+        assert_eq!(Error::new(DnsMsgError::InvalidData, "Mandatory SvcParam key123 is missing"), invalid("1 . mandatory=key123"));
+        assert_eq!(
+            Error::new(DnsMsgError::InvalidData, "SvcParam mandatory must not list itself"),
+            invalid("1 . mandatory=mandatory")
+        );
+        assert_eq!(
+            Error::new(DnsMsgError::InvalidData, "Keys in mandatory have to be in strictly increasing order"),
+            invalid("1 . alpn=h2 port=443 mandatory=port,alpn,port")
+        );
+
+        let unordered = Svcb { priority: 1, target: Name::root(), params: vec![SvcParam::Port(443), SvcParam::NoDefaultAlpn] };
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "SvcParam no-default-alpn has to come before port")),
+            unordered.validate()
+        );
+    }
+
+    #[test]
+    fn should_require_alpn_with_no_default_alpn() {
+        let expected = Error::new(DnsMsgError::InvalidData, "SvcParam no-default-alpn requires alpn");
+        assert_eq!(expected, invalid("1 . no-default-alpn port=443"));
+        assert!("1 . alpn=h3 no-default-alpn".parse::<Svcb>().is_ok());
+
+        let mut rdata = wire(&svcb("1 . alpn=h3 no-default-alpn"));
+        // drop the alpn parameter: key, length and "h3" as one id
+        rdata.drain(3..3 + 4 + 3);
+        let mut buffer = vec![0u8; 12];
+        write_u16(&mut buffer, &mut 6, 1);
+        buffer.extend(&[0, 0, 64, 0, 1, 0, 0, 0, 0]);
+        append_u16(&mut buffer, rdata.len() as u16);
+        buffer.extend(&rdata);
+        assert_eq!(Err(expected), ::parse(&buffer).map(|_| ()));
+    }
+
+    #[test]
+    fn should_round_trip_base64() {
+        for data in &[&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            assert_eq!(Some(data.to_vec()), base64_decode(base64_encode(data).as_bytes()));
+        }
+        assert_eq!("Zm9vYg==", base64_encode(b"foob"));
+        assert_eq!(None, base64_decode(b"Zg==Zm9v"));
+        assert_eq!(None, base64_decode(b"Zm9v!A=="));
+    }
+}
@@ -0,0 +1,442 @@
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::result;
+use std::str::FromStr;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use ::enums::{Type, ExtendedErrorCode};
+use ::errors::*;
+use ::utils::{append_u16, append_u32, write_u16};
+
+pub const CLIENT_SUBNET: u16 = 8;
+pub const COOKIE: u16 = 10;
+pub const EXTENDED_ERROR: u16 = 15;
+
+/// The EDNS(0) information carried by the OPT pseudo-record in the
+/// additional section of a message (RFC 6891).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Edns<'a> {
+    /// The largest UDP payload the sender is able to receive.
+    pub udp_payload_size: u16,
+    /// The upper eight bits of the 12 bit RCODE.
+    pub extended_rcode: u8,
+    pub version: u8,
+    /// DNSSEC OK, the sender is able to handle DNSSEC records.
+    pub dnssec_ok: bool,
+    /// The remaining, unassigned flag bits.
+    pub z: u16,
+    pub options: Vec<EdnsOption<'a>>,
+}
+
+impl<'a> Edns<'a> {
+    pub fn new(udp_payload_size: u16) -> Self {
+        Edns {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: vec![],
+        }
+    }
+
+    /// The first COOKIE option, if any.
+    pub fn cookie(&self) -> Option<&Cookie<'a>> {
+        self.options.iter()
+            .filter_map(|option| match *option {
+                EdnsOption::Cookie(ref cookie) => Some(cookie),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// All Extended DNS Error options, in the order they were given.
+    pub fn extended_errors(&self) -> impl Iterator<Item = &ExtendedError<'a>> {
+        self.options.iter()
+            .filter_map(|option| match *option {
+                EdnsOption::ExtendedError(ref error) => Some(error),
+                _ => None,
+            })
+    }
+
+    /// Append the OPT record to a message in `buffer`.
+    pub(crate) fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.push(0); // root
+        append_u16(buffer, u16::from(Type::OPT));
+        append_u16(buffer, self.udp_payload_size);
+
+        let mut ttl = (self.extended_rcode as u32) << 24;
+        ttl |= (self.version as u32) << 16;
+        ttl |= (self.dnssec_ok as u32) << 15;
+        ttl |= (self.z & 0x7fff) as u32;
+        append_u32(buffer, ttl);
+
+        let mut len_pos = buffer.len();
+        append_u16(buffer, 0);
+        for option in &self.options {
+            option.encode(buffer);
+        }
+        let len = buffer.len() - len_pos - 2;
+        write_u16(buffer, &mut len_pos, len as u16);
+    }
+
+    /// The number of bytes the OPT record takes in a message.
+    pub(crate) fn wire_len(&self) -> usize {
+        let mut buffer = vec![];
+        self.encode(&mut buffer);
+        buffer.len()
+    }
+
+    pub fn into_owned(self) -> Edns<'static> {
+        Edns {
+            udp_payload_size: self.udp_payload_size,
+            extended_rcode: self.extended_rcode,
+            version: self.version,
+            dnssec_ok: self.dnssec_ok,
+            z: self.z,
+            options: self.options.into_iter().map(EdnsOption::into_owned).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum EdnsOption<'a> {
+    ClientSubnet(ClientSubnet),
+    Cookie(Cookie<'a>),
+    ExtendedError(ExtendedError<'a>),
+    /// An option this library does not interpret, holding the option
+    /// code and its raw data.
+    Unknown(u16, Cow<'a, [u8]>),
+}
+
+impl<'a> EdnsOption<'a> {
+    pub fn code(&self) -> u16 {
+        match *self {
+            EdnsOption::ClientSubnet(_) => CLIENT_SUBNET,
+            EdnsOption::Cookie(_) => COOKIE,
+            EdnsOption::ExtendedError(_) => EXTENDED_ERROR,
+            EdnsOption::Unknown(code, _) => code,
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        append_u16(buffer, self.code());
+
+        let mut len_pos = buffer.len();
+        append_u16(buffer, 0);
+        match *self {
+            EdnsOption::ClientSubnet(ref subnet) => subnet.encode(buffer),
+            EdnsOption::Cookie(ref cookie) => {
+                buffer.extend(&cookie.client);
+                if let Some(ref server) = cookie.server {
+                    buffer.extend(server.iter());
+                }
+            },
+            EdnsOption::ExtendedError(ref error) => {
+                append_u16(buffer, u16::from(error.code));
+                buffer.extend(error.text.as_bytes());
+            },
+            EdnsOption::Unknown(_, ref data) => buffer.extend(data.iter()),
+        }
+        let len = buffer.len() - len_pos - 2;
+        write_u16(buffer, &mut len_pos, len as u16);
+    }
+
+    pub fn into_owned(self) -> EdnsOption<'static> {
+        match self {
+            EdnsOption::ClientSubnet(subnet) => EdnsOption::ClientSubnet(subnet),
+            EdnsOption::Cookie(cookie) => EdnsOption::Cookie(cookie.into_owned()),
+            EdnsOption::ExtendedError(error) => EdnsOption::ExtendedError(ExtendedError {
+                code: error.code,
+                text: Cow::Owned(error.text.into_owned()),
+            }),
+            EdnsOption::Unknown(code, data) => EdnsOption::Unknown(code, Cow::Owned(data.into_owned())),
+        }
+    }
+}
+
+/// The EDNS Client Subnet option (RFC 7871), the network a query
+/// originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ClientSubnet {
+    address: IpAddr,
+    source_prefix_len: u8,
+    scope_prefix_len: u8,
+}
+
+impl ClientSubnet {
+    /// Create the option for a query from the network `address` /
+    /// `source_prefix_len`. Address bits beyond the prefix are cleared.
+    pub fn new(address: IpAddr, source_prefix_len: u8) -> Result<'static, Self> {
+        ClientSubnet::with_scope(address, source_prefix_len, 0)
+    }
+
+    /// Create the option for a response, which additionally carries
+    /// the prefix length the answer is valid for.
+    pub fn with_scope(address: IpAddr, source_prefix_len: u8, scope_prefix_len: u8) -> Result<'static, Self> {
+        let max_len = max_prefix_len(&address);
+        if source_prefix_len > max_len || scope_prefix_len > max_len {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("Prefix length of client subnet {} exceeds {} bits", address, max_len)
+            ));
+        }
+
+        Ok(ClientSubnet {
+            address: mask(address, source_prefix_len),
+            source_prefix_len,
+            scope_prefix_len,
+        })
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub fn source_prefix_len(&self) -> u8 {
+        self.source_prefix_len
+    }
+
+    pub fn scope_prefix_len(&self) -> u8 {
+        self.scope_prefix_len
+    }
+
+    /// The address family as assigned by IANA, 1 for IPv4, 2 for IPv6.
+    pub fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(_) => 1,
+            IpAddr::V6(_) => 2,
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        append_u16(buffer, self.family());
+        buffer.push(self.source_prefix_len);
+        buffer.push(self.scope_prefix_len);
+
+        let len = (self.source_prefix_len as usize).div_ceil(8);
+        match self.address {
+            IpAddr::V4(address) => buffer.extend(&address.octets()[..len]),
+            IpAddr::V6(address) => buffer.extend(&address.octets()[..len]),
+        }
+    }
+}
+
+/// An Extended DNS Error (RFC 8914), explaining why a query failed or
+/// how an answer came about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedError<'a> {
+    pub code: ExtendedErrorCode,
+    /// Additional information for humans, possibly empty.
+    pub text: Cow<'a, str>,
+}
+
+impl<'a> ExtendedError<'a> {
+    pub fn new<T>(code: ExtendedErrorCode, text: T) -> Self
+        where T: Into<Cow<'a, str>>
+    {
+        ExtendedError { code, text: text.into() }
+    }
+}
+
+/// Serialized with both the numeric info code and its description,
+/// so the output is meaningful to operators.
+impl<'a> Serialize for ExtendedError<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ExtendedError", 3)?;
+        state.serialize_field("info_code", &u16::from(self.code))?;
+        state.serialize_field("purpose", &self.code.to_string())?;
+        state.serialize_field("extra_text", &self.text)?;
+        state.end()
+    }
+}
+
+/// The DNS Cookie option (RFC 7873), a client cookie optionally
+/// followed by the cookie the server handed out to this client.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Cookie<'a> {
+    client: [u8; 8],
+    server: Option<Cow<'a, [u8]>>,
+}
+
+impl<'a> Cookie<'a> {
+    pub fn new(client: [u8; 8]) -> Self {
+        Cookie { client, server: None }
+    }
+
+    /// Create a cookie including a server cookie, which has to be
+    /// between 8 and 32 bytes long.
+    pub fn with_server<S>(client: [u8; 8], server: S) -> Result<'static, Self>
+        where S: Into<Cow<'a, [u8]>>
+    {
+        let server = server.into();
+        if !(8..=32).contains(&server.len()) {
+            return Err(Error::new(
+                DnsMsgError::InvalidData,
+                format!("Server cookie of {} bytes is not between 8 and 32 bytes long", server.len())
+            ));
+        }
+
+        Ok(Cookie { client, server: Some(server) })
+    }
+
+    pub fn client(&self) -> &[u8; 8] {
+        &self.client
+    }
+
+    pub fn server(&self) -> Option<&[u8]> {
+        self.server.as_ref().map(|s| s.as_ref())
+    }
+
+    pub fn into_owned(self) -> Cookie<'static> {
+        Cookie {
+            client: self.client,
+            server: self.server.map(|s| Cow::Owned(s.into_owned())),
+        }
+    }
+}
+
+impl FromStr for ClientSubnet {
+    type Err = Error<'static>;
+
+    /// Parse `address/prefix length`, where an address without prefix
+    /// length stands for itself only.
+    fn from_str(s: &str) -> Result<'static, Self> {
+        let mut parts = s.splitn(2, '/');
+        let address = parts.next().unwrap().parse::<IpAddr>()
+            .map_err(|_| Error::new(DnsMsgError::InvalidData, format!("Invalid client subnet address {}", s)))?;
+        let source_prefix_len = match parts.next() {
+            Some(len) => len.parse::<u8>()
+                .map_err(|_| Error::new(DnsMsgError::InvalidData, format!("Invalid client subnet prefix length {}", s)))?,
+            None => max_prefix_len(&address),
+        };
+
+        ClientSubnet::new(address, source_prefix_len)
+    }
+}
+
+fn max_prefix_len(address: &IpAddr) -> u8 {
+    match *address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Clear all bits of `address` beyond the first `prefix_len` ones.
+pub(crate) fn mask(address: IpAddr, prefix_len: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(address) => {
+            let mut octets = address.octets();
+            mask_octets(&mut octets, prefix_len);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        },
+        IpAddr::V6(address) => {
+            let mut octets = address.octets();
+            mask_octets(&mut octets, prefix_len);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        },
+    }
+}
+
+fn mask_octets(octets: &mut [u8], prefix_len: u8) {
+    for (i, octet) in octets.iter_mut().enumerate() {
+        let bits = (prefix_len as usize).saturating_sub(i * 8);
+        if bits < 8 {
+            *octet &= !(0xffu8 >> bits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate serde_json;
+
+    use super::*;
+
+    fn encode(option: EdnsOption) -> Vec<u8> {
+        let mut buffer = vec![];
+        option.encode(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn should_clear_address_bits_beyond_the_prefix() {
+        let subnet = ClientSubnet::new("192.0.2.255".parse().unwrap(), 23).unwrap();
+        assert_eq!("192.0.2.0".parse::<IpAddr>().unwrap(), subnet.address());
+
+        let subnet = ClientSubnet::new("2001:db8:ffff::1".parse().unwrap(), 36).unwrap();
+        assert_eq!("2001:db8:f000::".parse::<IpAddr>().unwrap(), subnet.address());
+
+        let subnet = ClientSubnet::new("192.0.2.1".parse().unwrap(), 0).unwrap();
+        assert_eq!("0.0.0.0".parse::<IpAddr>().unwrap(), subnet.address());
+    }
+
+    #[test]
+    fn should_reject_long_prefixes() {
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "Prefix length of client subnet 192.0.2.1 exceeds 32 bits"));
+        assert_eq!(expected, ClientSubnet::new("192.0.2.1".parse().unwrap(), 33));
+        assert!(ClientSubnet::with_scope("2001:db8::".parse().unwrap(), 32, 129).is_err());
+    }
+
+    #[test]
+    fn should_parse_client_subnets() {
+        let subnet: ClientSubnet = "192.0.2.1/24".parse().unwrap();
+        assert_eq!("192.0.2.0".parse::<IpAddr>().unwrap(), subnet.address());
+        assert_eq!(24, subnet.source_prefix_len());
+        assert_eq!(0, subnet.scope_prefix_len());
+
+        assert_eq!(128, "2001:db8::1".parse::<ClientSubnet>().unwrap().source_prefix_len());
+        assert!("example.com/24".parse::<ClientSubnet>().is_err());
+        assert!("192.0.2.0/x".parse::<ClientSubnet>().is_err());
+    }
+
+    #[test]
+    fn should_encode_truncated_addresses() {
+        let subnet = ClientSubnet::new("192.0.2.1".parse().unwrap(), 23).unwrap();
+        assert_eq!(vec![0, 8, 0, 7, 0, 1, 23, 0, 192, 0, 2], encode(EdnsOption::ClientSubnet(subnet)));
+
+        let subnet = ClientSubnet::with_scope("2001:db8::".parse().unwrap(), 32, 48).unwrap();
+        assert_eq!(vec![0, 8, 0, 8, 0, 2, 32, 48, 0x20, 0x01, 0x0d, 0xb8], encode(EdnsOption::ClientSubnet(subnet)));
+
+        let subnet = ClientSubnet::new("192.0.2.1".parse().unwrap(), 0).unwrap();
+        assert_eq!(vec![0, 8, 0, 4, 0, 1, 0, 0], encode(EdnsOption::ClientSubnet(subnet)));
+    }
+
+    #[test]
+    fn should_encode_cookies() {
+        let cookie = Cookie::new([1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(vec![0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8], encode(EdnsOption::Cookie(cookie)));
+
+        let cookie = Cookie::with_server([1; 8], vec![2; 16]).unwrap();
+        let mut expected = vec![0, 10, 0, 24];
+        expected.extend(&[1; 8]);
+        expected.extend(&[2; 16]);
+        assert_eq!(expected, encode(EdnsOption::Cookie(cookie)));
+    }
+
+    #[test]
+    fn should_encode_extended_errors() {
+        let error = ExtendedError::new(ExtendedErrorCode::StaleAnswer, "");
+        assert_eq!(vec![0, 15, 0, 2, 0, 3], encode(EdnsOption::ExtendedError(error)));
+
+        let error = ExtendedError::new(ExtendedErrorCode::Blocked, "ads");
+        assert_eq!(vec![0, 15, 0, 5, 0, 15, b'a', b'd', b's'], encode(EdnsOption::ExtendedError(error)));
+    }
+
+    #[test]
+    fn should_serialize_extended_errors_with_their_purpose() {
+        let error = ExtendedError::new(ExtendedErrorCode::NoReachableAuthority, "at delegation example.com.");
+        assert_eq!(
+            r#"{"info_code":22,"purpose":"No Reachable Authority","extra_text":"at delegation example.com."}"#,
+            serde_json::to_string(&error).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_server_cookies() {
+        let expected = Err(Error::new(DnsMsgError::InvalidData, "Server cookie of 7 bytes is not between 8 and 32 bytes long"));
+        assert_eq!(expected, Cookie::with_server([0; 8], vec![0; 7]));
+        assert!(Cookie::with_server([0; 8], vec![0; 33]).is_err());
+        assert!(Cookie::with_server([0; 8], vec![0; 32]).is_ok());
+    }
+}
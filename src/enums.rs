@@ -1,3 +1,7 @@
+use std::fmt;
+use std::str::FromStr;
+use ::errors::*;
+
 enum_from_primitive! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
     pub enum QR {
@@ -6,92 +10,335 @@ enum_from_primitive! {
     }
 }
 
-enum_from_primitive! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum OPCODE {
-        QUERY  = 0,
-        IQUERY = 1,
-        STATUS = 2
+/// Declares an enum over a 16 bit DNS parameter. Values without a named
+/// variant are kept in `Unknown` so they survive a round trip, and are
+/// presented as `<prefix><value>`, e.g. `TYPE1234` (RFC 3597).
+macro_rules! dns_enum {
+    (
+        pub enum $name:ident ($prefix:expr) {
+            $($variant:ident = $value:literal => $mnemonic:expr),+
+        }
+    ) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+        pub enum $name {
+            $($variant,)+
+            Unknown(u16)
+        }
+
+        impl From<u16> for $name {
+            fn from(val: u16) -> Self {
+                match val {
+                    $($value => $name::$variant,)+
+                    _ => $name::Unknown(val)
+                }
+            }
+        }
+
+        impl From<$name> for u16 {
+            fn from(val: $name) -> Self {
+                match val {
+                    $($name::$variant => $value,)+
+                    $name::Unknown(val) => val
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $($name::$variant => f.write_str($mnemonic),)+
+                    $name::Unknown(val) => write!(f, "{}{}", $prefix, val)
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error<'static>;
+
+            fn from_str(s: &str) -> Result<'static, Self> {
+                $(
+                    if s.eq_ignore_ascii_case($mnemonic) {
+                        return Ok($name::$variant);
+                    }
+                )+
+
+                // compare bytes, as the prefix length needn't fall on a
+                // character boundary of `s`
+                let prefix: &str = $prefix;
+                if s.len() > prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes()) {
+                    if let Ok(val) = s[prefix.len()..].parse::<u16>() {
+                        return Ok($name::from(val));
+                    }
+                }
+
+                Err(Error::new(
+                    DnsMsgError::InvalidData,
+                    format!("Unknown {} {}", stringify!($name), s)
+                ))
+            }
+        }
     }
 }
 
-enum_from_primitive! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum RCODE {
-        Ok              = 0,
-        FormatError     = 1,
-        ServerFailure   = 2,
-        NameError       = 3,
-        NotImplemented  = 4,
-        Refused         = 5
+dns_enum! {
+    pub enum Type ("TYPE") {
+        A        =   1 => "A",
+        NS       =   2 => "NS",
+        MD       =   3 => "MD",
+        MF       =   4 => "MF",
+        CNAME    =   5 => "CNAME",
+        SOA      =   6 => "SOA",
+        MB       =   7 => "MB",
+        MG       =   8 => "MG",
+        MR       =   9 => "MR",
+        NULL     =  10 => "NULL",
+        WKS      =  11 => "WKS",
+        PTR      =  12 => "PTR",
+        HINFO    =  13 => "HINFO",
+        MINFO    =  14 => "MINFO",
+        MX       =  15 => "MX",
+        TXT      =  16 => "TXT",
+        AAAA     =  28 => "AAAA",
+        SRV      =  33 => "SRV",
+        OPT      =  41 => "OPT",
+        SVCB     =  64 => "SVCB",
+        HTTPS    =  65 => "HTTPS",
+        TSIG     = 250 => "TSIG",
+        Wildcard = 255 => "ANY",
+        CAA      = 257 => "CAA"
     }
 }
 
-enum_from_primitive! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum Type {
-        A        =   1,
-        NS       =   2,
-        MD       =   3,
-        MF       =   4,
-        CNAME    =   5,
-        SOA      =   6,
-        MB       =   7,
-        MG       =   8,
-        MR       =   9,
-        NULL     =  10,
-        WKS      =  11,
-        PTR      =  12,
-        HINFO    =  13,
-        MINFO    =  14,
-        MX       =  15,
-        TXT      =  16
+dns_enum! {
+    pub enum Qtype ("TYPE") {
+        A        =   1 => "A",
+        NS       =   2 => "NS",
+        MD       =   3 => "MD",
+        MF       =   4 => "MF",
+        CNAME    =   5 => "CNAME",
+        SOA      =   6 => "SOA",
+        MB       =   7 => "MB",
+        MG       =   8 => "MG",
+        MR       =   9 => "MR",
+        NULL     =  10 => "NULL",
+        WKS      =  11 => "WKS",
+        PTR      =  12 => "PTR",
+        HINFO    =  13 => "HINFO",
+        MINFO    =  14 => "MINFO",
+        MX       =  15 => "MX",
+        TXT      =  16 => "TXT",
+        AAAA     =  28 => "AAAA",
+        SRV      =  33 => "SRV",
+        OPT      =  41 => "OPT",
+        SVCB     =  64 => "SVCB",
+        HTTPS    =  65 => "HTTPS",
+        TSIG     = 250 => "TSIG",
+        AXFR     = 252 => "AXFR",
+        MAILB    = 253 => "MAILB",
+        MAILA    = 254 => "MAILA",
+        Wildcard = 255 => "ANY",
+        CAA      = 257 => "CAA"
     }
 }
 
-enum_from_primitive! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum Qtype {
-        A        =   1,
-        NS       =   2,
-        MD       =   3,
-        MF       =   4,
-        CNAME    =   5,
-        SOA      =   6,
-        MB       =   7,
-        MG       =   8,
-        MR       =   9,
-        NULL     =  10,
-        WKS      =  11,
-        PTR      =  12,
-        HINFO    =  13,
-        MINFO    =  14,
-        MX       =  15,
-        TXT      =  16,
-        AXFR     = 252,
-        MAILB    = 253,
-        MAILA    = 254,
-        Wildcard = 255
+dns_enum! {
+    pub enum Class ("CLASS") {
+        IN          =   1 => "IN",
+        CS          =   2 => "CS",
+        CH          =   3 => "CH",
+        HS          =   4 => "HS",
+        NONE        = 254 => "NONE",
+        Wildcard    = 255 => "ANY"
     }
 }
 
-enum_from_primitive! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum Class {
-        IN          =   1,
-        CS          =   2,
-        CH          =   3,
-        HS          =   4
+dns_enum! {
+    pub enum Qclass ("CLASS") {
+        IN          =   1 => "IN",
+        CS          =   2 => "CS",
+        CH          =   3 => "CH",
+        HS          =   4 => "HS",
+        Wildcard    = 255 => "ANY"
     }
 }
 
-enum_from_primitive!{
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
-    pub enum Qclass {
-        IN          =   1,
-        CS          =   2,
-        CH          =   3,
-        HS          =   4,
-        Wildcard    = 255
+dns_enum! {
+    pub enum OPCODE ("OPCODE") {
+        QUERY   = 0 => "QUERY",
+        IQUERY  = 1 => "IQUERY",
+        STATUS  = 2 => "STATUS",
+        NOTIFY  = 4 => "NOTIFY",
+        UPDATE  = 5 => "UPDATE",
+        DSO     = 6 => "DSO"
+    }
+}
+
+impl OPCODE {
+    /// The names of the four message sections. UPDATE messages use them
+    /// for the zone, prerequisites, updates and additional data
+    /// (RFC 2136).
+    pub fn section_names(&self) -> [&'static str; 4] {
+        match *self {
+            OPCODE::UPDATE => ["ZONE", "PREREQUISITE", "UPDATE", "ADDITIONAL"],
+            _ => ["QUESTION", "ANSWER", "AUTHORITY", "ADDITIONAL"],
+        }
+    }
+}
+
+dns_enum! {
+    pub enum RCODE ("RCODE") {
+        Ok              =  0 => "NOERROR",
+        FormatError     =  1 => "FORMERR",
+        ServerFailure   =  2 => "SERVFAIL",
+        NameError       =  3 => "NXDOMAIN",
+        NotImplemented  =  4 => "NOTIMP",
+        Refused         =  5 => "REFUSED",
+        YXDomain        =  6 => "YXDOMAIN",
+        YXRRSet         =  7 => "YXRRSET",
+        NXRRSet         =  8 => "NXRRSET",
+        NotAuth         =  9 => "NOTAUTH",
+        NotZone         = 10 => "NOTZONE",
+        DSOTypeNI       = 11 => "DSOTYPENI",
+        BadVers         = 16 => "BADVERS",
+        BadKey          = 17 => "BADKEY",
+        BadTime         = 18 => "BADTIME",
+        BadMode         = 19 => "BADMODE",
+        BadName         = 20 => "BADNAME",
+        BadAlg          = 21 => "BADALG",
+        BadTrunc        = 22 => "BADTRUNC",
+        BadCookie       = 23 => "BADCOOKIE"
+    }
+}
+
+impl RCODE {
+    /// TSIG reuses 16 for a bad signature, which only appears in the
+    /// error field of a TSIG record.
+    pub const BADSIG: RCODE = RCODE::BadVers;
+
+    /// Whether the RCODE needs the upper bits carried by an OPT
+    /// record, i.e. doesn't fit into the header's four bits.
+    pub fn is_extended(&self) -> bool {
+        u16::from(*self) > 0x0f
+    }
+}
+
+dns_enum! {
+    pub enum SvcParamKey ("key") {
+        Mandatory       = 0 => "mandatory",
+        Alpn            = 1 => "alpn",
+        NoDefaultAlpn   = 2 => "no-default-alpn",
+        Port            = 3 => "port",
+        Ipv4Hint        = 4 => "ipv4hint",
+        Ech             = 5 => "ech",
+        Ipv6Hint        = 6 => "ipv6hint",
+        DohPath         = 7 => "dohpath"
+    }
+}
+
+dns_enum! {
+    pub enum ExtendedErrorCode ("EDE") {
+        Other                      =  0 => "Other Error",
+        UnsupportedDnskeyAlgorithm =  1 => "Unsupported DNSKEY Algorithm",
+        UnsupportedDsDigestType    =  2 => "Unsupported DS Digest Type",
+        StaleAnswer                =  3 => "Stale Answer",
+        ForgedAnswer               =  4 => "Forged Answer",
+        DnssecIndeterminate        =  5 => "DNSSEC Indeterminate",
+        DnssecBogus                =  6 => "DNSSEC Bogus",
+        SignatureExpired           =  7 => "Signature Expired",
+        SignatureNotYetValid       =  8 => "Signature Not Yet Valid",
+        DnskeyMissing              =  9 => "DNSKEY Missing",
+        RrsigsMissing              = 10 => "RRSIGs Missing",
+        NoZoneKeyBitSet            = 11 => "No Zone Key Bit Set",
+        NsecMissing                = 12 => "NSEC Missing",
+        CachedError                = 13 => "Cached Error",
+        NotReady                   = 14 => "Not Ready",
+        Blocked                    = 15 => "Blocked",
+        Censored                   = 16 => "Censored",
+        Filtered                   = 17 => "Filtered",
+        Prohibited                 = 18 => "Prohibited",
+        StaleNxdomainAnswer        = 19 => "Stale NXDOMAIN Answer",
+        NotAuthoritative           = 20 => "Not Authoritative",
+        NotSupported               = 21 => "Not Supported",
+        NoReachableAuthority       = 22 => "No Reachable Authority",
+        NetworkError               = 23 => "Network Error",
+        InvalidData                = 24 => "Invalid Data"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_convert_known_values() {
+        assert_eq!(Type::AAAA, Type::from(28));
+        assert_eq!(28, u16::from(Type::AAAA));
+        assert_eq!(Qclass::Wildcard, Qclass::from(255));
+    }
+
+    #[test]
+    fn should_keep_unknown_values() {
+        assert_eq!(Type::Unknown(1234), Type::from(1234));
+        assert_eq!(1234, u16::from(Type::Unknown(1234)));
+        assert_eq!(Class::Unknown(253), Class::from(253));
+    }
+
+    #[test]
+    fn should_display_mnemonics_and_generic_form() {
+        assert_eq!("MX", Type::MX.to_string());
+        assert_eq!("TYPE1234", Type::from(1234).to_string());
+        assert_eq!("ANY", Qtype::Wildcard.to_string());
+        assert_eq!("CLASS42", Class::from(42).to_string());
+    }
+
+    #[test]
+    fn should_parse_mnemonics_and_generic_form() {
+        assert_eq!(Ok(Type::MX), "mx".parse());
+        assert_eq!(Ok(Type::A), "TYPE1".parse());
+        assert_eq!(Ok(Type::Unknown(1234)), "TYPE1234".parse());
+        assert_eq!(Ok(Qclass::Wildcard), "ANY".parse());
+        assert_eq!(Ok(Class::Unknown(42)), "class42".parse());
+
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Unknown Type TYPE")),
+            "TYPE".parse::<Type>()
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Unknown Class FOO")),
+            "FOO".parse::<Class>()
+        );
+    }
+
+    #[test]
+    fn should_reject_non_ascii_input() {
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Unknown Type TYPÉ1")),
+            "TYPÉ1".parse::<Type>()
+        );
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Unknown SvcParamKey keé1")),
+            "keé1".parse::<SvcParamKey>()
+        );
+        assert!("RCOD€".parse::<RCODE>().is_err());
+        assert!("ED€1".parse::<ExtendedErrorCode>().is_err());
+    }
+
+    #[test]
+    fn should_cover_extended_rcodes() {
+        assert_eq!(RCODE::BadCookie, RCODE::from(23));
+        assert_eq!(RCODE::BADSIG, RCODE::from(16));
+        assert_eq!(RCODE::Unknown(12), RCODE::from(12));
+        assert_eq!("NXDOMAIN", RCODE::NameError.to_string());
+        assert_eq!("RCODE4095", RCODE::from(4095).to_string());
+        assert!(RCODE::BadVers.is_extended());
+        assert!(!RCODE::DSOTypeNI.is_extended());
+    }
+
+    #[test]
+    fn should_name_update_sections() {
+        assert_eq!("ZONE", OPCODE::UPDATE.section_names()[0]);
+        assert_eq!("PREREQUISITE", OPCODE::UPDATE.section_names()[1]);
+        assert_eq!("ANSWER", OPCODE::NOTIFY.section_names()[1]);
+        assert_eq!(OPCODE::Unknown(9), OPCODE::from(9));
     }
 }
@@ -4,7 +4,11 @@ use std::borrow::Cow;
 #[derive(Debug, PartialEq, Eq, Serialize)]
 pub enum DnsMsgError {
     InvalidData,
-    CyclicLabelRef
+    CyclicLabelRef,
+    Truncated,
+    PointerOutOfBounds,
+    LabelTooLong,
+    NameTooLong
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize)]
@@ -21,6 +25,13 @@ impl<'a> Error<'a> {
             msg: msg.into()
         }
     }
+
+    pub fn into_owned(self) -> Error<'static> {
+        Error {
+            kind: self.kind,
+            msg: Cow::Owned(self.msg.into_owned())
+        }
+    }
 }
 
 pub type Result<'a, T> = result::Result<T, Error<'a>>;
@@ -1,24 +1,47 @@
 use std::collections::HashMap;
+use ::name::Name;
+use utils::append_u16;
 
+/// Label references are 14 bit offsets, names written beyond that
+/// can't be referred to.
+const MAX_LABEL_REF: usize = 0x3fff;
+
+/// Encode `name` as it would be written at `pos` in a message. The
+/// longest suffix of `name` already present in `encoded_labels` is
+/// replaced by a label reference, and all suffixes written here are
+/// added to the map for subsequent names. Suffixes only match if their
+/// case matches too, so that the name parses back unchanged.
 pub fn encode_labels<'a>(
-    encoded_labels: &mut HashMap<&'a str, usize>,
+    encoded_labels: &mut HashMap<Name<'a>, usize>,
     pos: usize,
-    address: &'a str
+    name: &Name<'a>
 ) -> Vec<u8> {
     let mut ret: Vec<u8> = vec![];
 
-    if encoded_labels.contains_key(address) {
-        let jump_addr = encoded_labels.get(address).unwrap();
-        ret.push(0xc0);
-        ret.push(*jump_addr as u8);
-    } else {
-        encoded_labels.insert(address, pos);
-        for label in address.split('.') {
-            ret.push(label.as_bytes().len() as u8);
-            ret.extend(label.as_bytes());
+    let mut suffix = name.clone();
+    while let Some(parent) = suffix.parent() {
+        // the map's keys compare case-insensitively, so a differently
+        // cased suffix may be found, but neither reused nor replaced
+        let known = match encoded_labels.get_key_value(&suffix) {
+            Some((encoded, &jump_addr)) if encoded.labels().eq(suffix.labels()) => {
+                append_u16(&mut ret, 0xc000 | jump_addr as u16);
+                return ret;
+            },
+            Some(_) => true,
+            None => false,
+        };
+
+        let offset = pos + ret.len();
+        let label = suffix.labels().next().unwrap().to_vec();
+        if !known && offset <= MAX_LABEL_REF {
+            encoded_labels.insert(suffix, offset);
         }
-        ret.push(0);
+
+        ret.push(label.len() as u8);
+        ret.extend(label);
+        suffix = parent;
     }
+    ret.push(0);
 
     ret
 }
@@ -27,32 +50,83 @@ pub fn encode_labels<'a>(
 mod test {
     use super::*;
 
+    fn name(address: &str) -> Name<'static> {
+        address.parse().unwrap()
+    }
+
     #[test]
     fn encode_labels_should_prepend_byte_length() {
-        assert_eq!(4, encode_labels(&mut HashMap::new(), 0, "abcd")[0]);
-        assert_eq!(3, encode_labels(&mut HashMap::new(), 0, "xxx")[0]);
+        assert_eq!(4, encode_labels(&mut HashMap::new(), 0, &name("abcd"))[0]);
+        assert_eq!(3, encode_labels(&mut HashMap::new(), 0, &name("xxx"))[0]);
     }
 
     #[test]
     fn encode_labels_should_encode_string() {
-        let encoded = encode_labels(&mut HashMap::new(), 0, "abcd.aao");
+        let encoded = encode_labels(&mut HashMap::new(), 0, &name("abcd.aao"));
         assert_eq!(&encoded, &[4, 97, 98, 99, 100, 3, 97, 97, 111, 0]);
     }
 
+    #[test]
+    fn encode_labels_should_encode_root() {
+        assert_eq!(&encode_labels(&mut HashMap::new(), 0, &Name::root()), &[0]);
+    }
+
     #[test]
     fn encode_label_should_add_data_to_map() {
         let mut map = HashMap::new();
-        encode_labels(&mut map, 5, "aa.bb");
+        encode_labels(&mut map, 5, &name("aa.bb"));
 
-        assert_eq!(Some(&5usize), map.get("aa.bb"));
+        assert_eq!(Some(&5usize), map.get(&name("aa.bb")));
+        assert_eq!(Some(&8usize), map.get(&name("bb")));
     }
 
     #[test]
     fn encode_label_should_write_ref() {
         let mut map = HashMap::new();
-        map.insert("aa.bb", 5);
+        map.insert(name("aa.bb"), 5);
 
-        let buffer = encode_labels(&mut map, 0, "aa.bb");
+        let buffer = encode_labels(&mut map, 0, &name("aa.bb"));
         assert_eq!(&buffer[0..2], &[0xc0, 5]);
     }
+
+    #[test]
+    fn encode_label_should_write_ref_to_suffix() {
+        let mut map = HashMap::new();
+        encode_labels(&mut map, 12, &name("example.com"));
+
+        let buffer = encode_labels(&mut map, 40, &name("mail.example.com"));
+        assert_eq!(&buffer, &[4, b'm', b'a', b'i', b'l', 0xc0, 12]);
+        assert_eq!(Some(&40usize), map.get(&name("mail.example.com")));
+    }
+
+    #[test]
+    fn encode_label_should_write_ref_only_if_case_matches() {
+        let mut map = HashMap::new();
+        encode_labels(&mut map, 12, &name("Example.COM"));
+
+        let buffer = encode_labels(&mut map, 40, &name("www.example.COM"));
+        assert_eq!(&buffer, &[3, b'w', b'w', b'w', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0xc0, 20]);
+        assert_eq!(Some(&12usize), map.get(&name("example.com")));
+
+        let buffer = encode_labels(&mut map, 60, &name("www.Example.COM"));
+        assert_eq!(&buffer, &[3, b'w', b'w', b'w', 0xc0, 12]);
+    }
+
+    #[test]
+    fn encode_label_should_write_14_bit_refs() {
+        let mut map = HashMap::new();
+        map.insert(name("aa.bb"), 0x1234);
+
+        let buffer = encode_labels(&mut map, 0x2000, &name("aa.bb"));
+        assert_eq!(&buffer, &[0xd2, 0x34]);
+    }
+
+    #[test]
+    fn encode_label_should_not_refer_beyond_14_bits() {
+        let mut map = HashMap::new();
+        encode_labels(&mut map, 0x3ffd, &name("aa.bb"));
+
+        assert_eq!(Some(&0x3ffdusize), map.get(&name("aa.bb")));
+        assert_eq!(None, map.get(&name("bb")));
+    }
 }
@@ -1,25 +1,51 @@
 extern crate num;
 #[macro_use] extern crate enum_primitive;
 extern crate rand;
+extern crate siphasher;
+extern crate hmac;
+extern crate sha2;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
 
 mod dns_record;
 mod builder;
+mod cookies;
+mod edns;
 mod enums;
 mod errors;
 mod labels;
+mod name;
+mod tsig;
+mod update;
 mod utils;
 
 pub type ParsedQuestion<'a> = dns_record::Question<'a>;
 
 pub use self::dns_record::{
+    DnsRecord,
+    Header,
     parse,
     Record,
     RecordPayload,
+    Soa,
+    Wks,
+    Hinfo,
+    Minfo,
+    Mx,
+    Srv,
+    Svcb,
+    SvcParam,
+    Caa,
+    CaaIssuer,
+    caa_allows_issuance,
 };
 
+pub use self::cookies::{CookieJar, ServerCookies, CookieStatus};
+pub use self::edns::{Edns, EdnsOption, ClientSubnet, Cookie, ExtendedError};
 pub use self::enums::*;
+pub use self::name::Name;
+pub use self::tsig::{Tsig, TsigAlgorithm, TsigKey, TsigSession, TsigError};
+pub use self::update::{UpdateBuilder, UpdateMessage, Prerequisite, UpdateOperation};
 pub use self::errors::*;
 pub use self::builder::*;
 pub use self::builder::Question;
@@ -0,0 +1,381 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::result;
+use std::str::{self, FromStr};
+use serde::{Serialize, Serializer};
+use ::errors::*;
+
+pub const MAX_LABEL_LEN: usize = 63;
+pub const MAX_NAME_LEN: usize = 255;
+
+/// A domain name, i.e. a sequence of labels ending at the root.
+///
+/// Labels are arbitrary octets. In their textual form, dots and other
+/// special characters within a label are escaped as `\.` and
+/// non-printable octets as `\DDD` (RFC 1035, section 5.1).
+///
+/// Names compare and hash case-insensitively (RFC 4343) and are ordered
+/// canonically as defined for DNSSEC (RFC 4034, section 6.1).
+#[derive(Debug, Clone)]
+pub struct Name<'a> {
+    labels: Vec<Cow<'a, [u8]>>
+}
+
+impl<'a> Name<'a> {
+    /// The root name `.`.
+    pub fn root() -> Self {
+        Name { labels: vec![] }
+    }
+
+    /// Parse a name from its textual form. Labels without escape
+    /// sequences are borrowed from `address`. A trailing dot is
+    /// optional, `.` denotes the root.
+    pub fn new(address: &'a str) -> Result<'static, Self> {
+        if address == "." || address.is_empty() {
+            return Ok(Name::root());
+        }
+
+        let bytes = address.as_bytes();
+        let mut labels = vec![];
+        let mut start = 0;
+        let mut escaped: Option<Vec<u8>> = None;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            match bytes[i] {
+                b'.' => {
+                    labels.push(match escaped.take() {
+                        Some(label) => Cow::Owned(label),
+                        None => Cow::Borrowed(&bytes[start..i])
+                    });
+                    i += 1;
+                    start = i;
+                    if i == bytes.len() {
+                        return Name::from_labels(labels);
+                    }
+                },
+                b'\\' => {
+                    let label = escaped.get_or_insert_with(|| bytes[start..i].to_vec());
+                    let (byte, len) = unescape(&bytes[i + 1..])?;
+                    label.push(byte);
+                    i += 1 + len;
+                },
+                byte => {
+                    if let Some(ref mut label) = escaped {
+                        label.push(byte);
+                    }
+                    i += 1;
+                }
+            }
+        }
+
+        labels.push(match escaped.take() {
+            Some(label) => Cow::Owned(label),
+            None => Cow::Borrowed(&bytes[start..])
+        });
+        Name::from_labels(labels)
+    }
+
+    /// Build a name from its raw labels, leftmost label first.
+    pub fn from_labels<L>(labels: L) -> Result<'static, Self>
+    where L: IntoIterator,
+          L::Item: Into<Cow<'a, [u8]>>
+    {
+        let labels: Vec<Cow<'a, [u8]>> = labels.into_iter()
+            .map(|l| l.into())
+            .collect();
+
+        let mut name_len = 1;
+        for label in &labels {
+            if label.is_empty() {
+                return Err(Error::new(DnsMsgError::InvalidData, "Names must not contain empty labels"));
+            }
+            if label.len() > MAX_LABEL_LEN {
+                let mut text = String::new();
+                write_label(&mut text, label).unwrap();
+                return Err(Error::new(
+                    DnsMsgError::LabelTooLong,
+                    format!("Label {} exceeds {} bytes", text, MAX_LABEL_LEN)
+                ));
+            }
+            name_len += label.len() + 1;
+        }
+        if name_len > MAX_NAME_LEN {
+            return Err(Error::new(
+                DnsMsgError::NameTooLong,
+                format!("Name exceeds {} bytes", MAX_NAME_LEN)
+            ));
+        }
+
+        Ok(Name { labels })
+    }
+
+    /// Build a name from labels which are known to be valid, e.g.
+    /// because the parser checked them already.
+    pub(crate) fn from_labels_unchecked(labels: Vec<Cow<'a, [u8]>>) -> Self {
+        Name { labels }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Iterate over the raw labels, leftmost label first. The root has
+    /// no labels.
+    pub fn labels(&self) -> impl Iterator<Item = &[u8]> {
+        self.labels.iter().map(|l| l.as_ref())
+    }
+
+    /// The number of bytes this name takes in a message when written
+    /// without label references.
+    pub fn wire_len(&self) -> usize {
+        self.labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1
+    }
+
+    /// The name with its leftmost label removed, `None` for the root.
+    pub fn parent(&self) -> Option<Name<'a>> {
+        if self.is_root() {
+            None
+        } else {
+            Some(Name { labels: self.labels[1..].to_vec() })
+        }
+    }
+
+    /// Whether `self` is `other` or lies below it.
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        if self.labels.len() < other.labels.len() {
+            return false;
+        }
+
+        let offset = self.labels.len() - other.labels.len();
+        self.labels[offset..].iter()
+            .zip(other.labels.iter())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    pub fn into_owned(self) -> Name<'static> {
+        Name {
+            labels: self.labels.into_iter()
+                .map(|l| Cow::Owned(l.into_owned()))
+                .collect()
+        }
+    }
+}
+
+/// Decode the escape sequence following a backslash, returning the
+/// octet and the number of bytes consumed. Shared by all presentation
+/// formats escaping as RFC 1035, section 5.1 does.
+pub(crate) fn unescape(bytes: &[u8]) -> Result<'static, (u8, usize)> {
+    match bytes.first() {
+        None => Err(Error::new(DnsMsgError::InvalidData, "Incomplete escape sequence at the end of the text")),
+        Some(byte) if byte.is_ascii_digit() => {
+            let digits = bytes.iter()
+                .take(3)
+                .take_while(|b| b.is_ascii_digit())
+                .fold(Vec::new(), |mut digits, b| { digits.push(*b); digits });
+            let value = str::from_utf8(&digits).unwrap().parse::<u16>().unwrap();
+            if digits.len() != 3 || value > 255 {
+                return Err(Error::new(
+                    DnsMsgError::InvalidData,
+                    "Escaped octets have to be given as three digits between 000 and 255"
+                ));
+            }
+            Ok((value as u8, 3))
+        },
+        Some(&byte) => Ok((byte, 1))
+    }
+}
+
+fn write_label<W: fmt::Write>(w: &mut W, label: &[u8]) -> fmt::Result {
+    for &byte in label {
+        match byte {
+            b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => write!(w, "\\{}", byte as char)?,
+            0x21..=0x7e => w.write_char(byte as char)?,
+            _ => write!(w, "\\{:03}", byte)?,
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Name<'static> {
+    type Err = Error<'static>;
+
+    fn from_str(s: &str) -> Result<'static, Self> {
+        Name::new(s).map(|name| name.into_owned())
+    }
+}
+
+impl<'a> fmt::Display for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str(".");
+        }
+        for label in &self.labels {
+            write_label(f, label)?;
+            f.write_str(".")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Serialize for Name<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a, 'b> PartialEq<Name<'b>> for Name<'a> {
+    fn eq(&self, other: &Name<'b>) -> bool {
+        self.labels.len() == other.labels.len()
+            && self.labels.iter()
+                .zip(other.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl<'a> Eq for Name<'a> {}
+
+impl<'a> Hash for Name<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in &self.labels {
+            state.write_u8(label.len() as u8);
+            for byte in label.iter() {
+                state.write_u8(byte.to_ascii_lowercase());
+            }
+        }
+    }
+}
+
+impl<'a> Ord for Name<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lower = |label: &Cow<[u8]>| label.to_ascii_lowercase();
+        let ours = self.labels.iter().rev().map(&lower);
+        let theirs = other.labels.iter().rev().map(&lower);
+        ours.cmp(theirs)
+    }
+}
+
+impl<'a> PartialOrd for Name<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn name(address: &str) -> Name<'static> {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn should_parse_names() {
+        let expected: Vec<&[u8]> = vec![b"www", b"example", b"com"];
+        assert_eq!(expected, name("www.example.com").labels().collect::<Vec<_>>());
+        assert_eq!(expected, name("www.example.com.").labels().collect::<Vec<_>>());
+        assert!(name(".").is_root());
+        assert!(name("").is_root());
+    }
+
+    #[test]
+    fn should_reject_invalid_names() {
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Names must not contain empty labels")),
+            "www..com".parse::<Name>()
+        );
+
+        let label = "a".repeat(64);
+        assert_eq!(
+            Err(Error::new(DnsMsgError::LabelTooLong, format!("Label {} exceeds 63 bytes", label))),
+            label.parse::<Name>()
+        );
+
+        let address = vec!["a".repeat(63); 4].join(".");
+        assert_eq!(
+            Err(Error::new(DnsMsgError::NameTooLong, "Name exceeds 255 bytes")),
+            address.parse::<Name>()
+        );
+        let address = ["a".repeat(63), "a".repeat(63), "a".repeat(63), "a".repeat(61)].join(".");
+        assert_eq!(255, name(&address).wire_len());
+    }
+
+    #[test]
+    fn should_display_with_trailing_dot() {
+        assert_eq!("www.example.com.", name("www.example.com").to_string());
+        assert_eq!(".", Name::root().to_string());
+    }
+
+    #[test]
+    fn should_parse_escaped_labels() {
+        let parsed = name("a\\.b.c\\\\d.\\000\\255x");
+        let expected: Vec<&[u8]> = vec![b"a.b", b"c\\d", b"\0\xffx"];
+        assert_eq!(expected, parsed.labels().collect::<Vec<_>>());
+
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Incomplete escape sequence at the end of the text")),
+            "example\\".parse::<Name>()
+        );
+        let message = "Escaped octets have to be given as three digits between 000 and 255";
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, message)), "a\\25".parse::<Name>());
+        assert_eq!(Err(Error::new(DnsMsgError::InvalidData, message)), "a\\256".parse::<Name>());
+    }
+
+    #[test]
+    fn should_display_escaped_labels() {
+        let labels: Vec<&[u8]> = vec![b"a.b", b"c\\d", b"\0 \xff", b"\"();@$"];
+        let name = Name::from_labels(labels).unwrap();
+        assert_eq!("a\\.b.c\\\\d.\\000\\032\\255.\\\"\\(\\)\\;\\@\\$.", name.to_string());
+        assert_eq!(name, Name::new(&name.to_string()).unwrap());
+    }
+
+    #[test]
+    fn should_compare_case_insensitively() {
+        assert_eq!(name("WWW.Example.COM"), name("www.example.com."));
+        assert!(name("www.example.com") != name("example.com"));
+
+        let mut set = HashSet::new();
+        set.insert(name("WWW.Example.COM"));
+        assert!(set.contains(&name("www.example.com")));
+    }
+
+    #[test]
+    fn should_order_canonically() {
+        // RFC 4034, section 6.1
+        let expected = vec![
+            name("example"),
+            name("a.example"),
+            name("yljkjljk.a.example"),
+            name("Z.a.example"),
+            name("zABC.a.EXAMPLE"),
+            name("z.example"),
+            name("*.z.example"),
+        ];
+        let mut names = expected.clone();
+        names.reverse();
+        names.sort();
+        assert_eq!(expected, names);
+
+        assert!(Name::root() < name("example"));
+    }
+
+    #[test]
+    fn should_find_subdomains() {
+        assert!(name("www.example.com").is_subdomain_of(&name("example.com")));
+        assert!(name("www.example.com").is_subdomain_of(&name("EXAMPLE.com")));
+        assert!(name("example.com").is_subdomain_of(&name("example.com")));
+        assert!(name("example.com").is_subdomain_of(&Name::root()));
+        assert!(!name("example.com").is_subdomain_of(&name("www.example.com")));
+        assert!(!name("badexample.com").is_subdomain_of(&name("example.com")));
+    }
+
+    #[test]
+    fn should_find_parents() {
+        assert_eq!(Some(name("example.com")), name("www.example.com").parent());
+        assert_eq!(Some(Name::root()), name("com").parent());
+        assert_eq!(None, Name::root().parent());
+    }
+}
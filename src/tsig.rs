@@ -0,0 +1,613 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+use std::result;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+use ::dns_record::{DnsRecord, parse_signed};
+use ::enums::{Type, Class, RCODE};
+use ::errors::*;
+use ::labels::encode_labels;
+use ::name::Name;
+use ::utils::{append_u16, append_u32, write_u16};
+
+/// The fudge RFC 8945 recommends, in seconds.
+const DEFAULT_FUDGE: u16 = 300;
+
+/// Clients accept up to 99 unsigned messages between two signed ones
+/// of a TCP stream (RFC 8945, section 5.3.1).
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+/// The TSIG record authenticating a message (RFC 8945).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Tsig<'a> {
+    pub key_name: Name<'a>,
+    pub algorithm: Name<'a>,
+    /// Seconds since the epoch, limited to 48 bits.
+    pub time_signed: u64,
+    /// The allowed difference in seconds between `time_signed` and the
+    /// receiver's clock.
+    pub fudge: u16,
+    pub mac: Cow<'a, [u8]>,
+    /// The message's ID when it was signed.
+    pub original_id: u16,
+    pub error: RCODE,
+    /// The server's time for `BadTime` errors.
+    pub other: Cow<'a, [u8]>,
+}
+
+impl<'a> Tsig<'a> {
+    /// Append the TSIG record to a message in `buffer`.
+    pub(crate) fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend(encode_labels(&mut HashMap::new(), 0, &self.key_name));
+        append_u16(buffer, u16::from(Type::TSIG));
+        append_u16(buffer, u16::from(Class::Wildcard));
+        append_u32(buffer, 0);
+
+        let mut len_pos = buffer.len();
+        append_u16(buffer, 0);
+        buffer.extend(encode_labels(&mut HashMap::new(), 0, &self.algorithm));
+        append_u48(buffer, self.time_signed);
+        append_u16(buffer, self.fudge);
+        append_u16(buffer, self.mac.len() as u16);
+        buffer.extend_from_slice(&self.mac);
+        append_u16(buffer, self.original_id);
+        append_u16(buffer, u16::from(self.error));
+        append_u16(buffer, self.other.len() as u16);
+        buffer.extend_from_slice(&self.other);
+        let len = buffer.len() - len_pos - 2;
+        write_u16(buffer, &mut len_pos, len as u16);
+    }
+
+    /// The TSIG variables covered by the MAC of a request or the first
+    /// response to it.
+    fn variables(&self) -> Vec<u8> {
+        let mut buffer = canonical(&self.key_name);
+        append_u16(&mut buffer, u16::from(Class::Wildcard));
+        append_u32(&mut buffer, 0);
+        buffer.extend(canonical(&self.algorithm));
+        buffer.extend(self.timers());
+        append_u16(&mut buffer, u16::from(self.error));
+        append_u16(&mut buffer, self.other.len() as u16);
+        buffer.extend_from_slice(&self.other);
+        buffer
+    }
+
+    /// The TSIG variables covered by the MAC of later messages of a
+    /// TCP stream.
+    fn timers(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        append_u48(&mut buffer, self.time_signed);
+        append_u16(&mut buffer, self.fudge);
+        buffer
+    }
+
+    pub fn into_owned(self) -> Tsig<'static> {
+        Tsig {
+            key_name: self.key_name.into_owned(),
+            algorithm: self.algorithm.into_owned(),
+            time_signed: self.time_signed,
+            fudge: self.fudge,
+            mac: Cow::Owned(self.mac.into_owned()),
+            original_id: self.original_id,
+            error: self.error,
+            other: Cow::Owned(self.other.into_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsigAlgorithm {
+    HmacSha256,
+    HmacSha512,
+}
+
+macro_rules! hmac {
+    ($digest:ty, $secret:expr, $data:expr) => {{
+        let mut mac = <Hmac<$digest> as Mac>::new_from_slice($secret)
+            .expect("HMAC takes keys of any length");
+        mac.update($data);
+        mac
+    }}
+}
+
+impl TsigAlgorithm {
+    pub fn name(&self) -> Name<'static> {
+        let name = match *self {
+            TsigAlgorithm::HmacSha256 => "hmac-sha256",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512",
+        };
+        Name::new(name).unwrap()
+    }
+
+    fn mac_len(&self) -> usize {
+        match *self {
+            TsigAlgorithm::HmacSha256 => 32,
+            TsigAlgorithm::HmacSha512 => 64,
+        }
+    }
+
+    fn sign(&self, secret: &[u8], data: &[u8]) -> Vec<u8> {
+        match *self {
+            TsigAlgorithm::HmacSha256 => hmac!(Sha256, secret, data).finalize().into_bytes().to_vec(),
+            TsigAlgorithm::HmacSha512 => hmac!(Sha512, secret, data).finalize().into_bytes().to_vec(),
+        }
+    }
+
+    /// Check `mac` in constant time. It may be truncated to its left
+    /// half, but no less than 10 bytes (RFC 8945, section 5.2.2.1).
+    fn verify(&self, secret: &[u8], data: &[u8], mac: &[u8]) -> bool {
+        match *self {
+            TsigAlgorithm::HmacSha256 => hmac!(Sha256, secret, data).verify_truncated_left(mac).is_ok(),
+            TsigAlgorithm::HmacSha512 => hmac!(Sha512, secret, data).verify_truncated_left(mac).is_ok(),
+        }
+    }
+}
+
+/// Why a message's TSIG record was not accepted. A server answers
+/// `BadKey`, `BadSig` and `BadTime` with `TsigKey::error_response`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TsigError {
+    /// The message or its TSIG record is malformed.
+    Malformed(Error<'static>),
+    /// The message is not signed, or a TCP stream ended with unsigned
+    /// messages.
+    Unsigned,
+    /// The message is signed with another key or algorithm.
+    BadKey,
+    BadSig,
+    /// The message was signed too long ago or too far in the future.
+    BadTime,
+    /// The peer rejected our message with the given TSIG error.
+    Rejected(RCODE),
+}
+
+/// A key shared with a peer, identified by its name.
+#[derive(Debug, Clone)]
+pub struct TsigKey {
+    name: Name<'static>,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+    fudge: u16,
+}
+
+impl TsigKey {
+    pub fn new(name: Name<'static>, algorithm: TsigAlgorithm, secret: &[u8]) -> Self {
+        TsigKey {
+            name,
+            algorithm,
+            secret: secret.to_vec(),
+            fudge: DEFAULT_FUDGE,
+        }
+    }
+
+    /// Set the allowed clock difference in seconds, 300 by default.
+    pub fn with_fudge(mut self, fudge: u16) -> Self {
+        self.fudge = fudge;
+        self
+    }
+
+    pub fn name(&self) -> &Name<'static> {
+        &self.name
+    }
+
+    pub fn algorithm(&self) -> TsigAlgorithm {
+        self.algorithm
+    }
+
+    /// Sign a request built by `DnsMessageBuilder` at `now` seconds since
+    /// the epoch. The returned session verifies the responses to it.
+    pub fn sign_request<'k>(&'k self, message: Vec<u8>, now: u64) -> Result<'static, (Vec<u8>, TsigSession<'k>)> {
+        let tsig = self.tsig(&message, now, RCODE::Ok, vec![])?;
+        let (message, mac) = self.append(message, &[], true, tsig)?;
+        Ok((message, TsigSession::new(self, mac)))
+    }
+
+    /// Verify a request passed to `parse`. The returned session signs
+    /// the responses to it.
+    pub fn verify_request<'k>(&'k self, data: &[u8], now: u64) -> result::Result<TsigSession<'k>, TsigError> {
+        match self.check(data, &[], true, now)? {
+            Some(mac) => Ok(TsigSession::new(self, mac)),
+            None => Err(TsigError::Unsigned),
+        }
+    }
+
+    /// Add a TSIG record reporting `error` to a response to `request`,
+    /// which should have the RCODE `NotAuth`. Only `BadTime` responses
+    /// are signed, as the other errors leave no key to sign with.
+    pub fn error_response(&self, message: Vec<u8>, request: &DnsRecord, error: &TsigError, now: u64) -> Result<'static, Vec<u8>> {
+        let request_tsig = match request.tsig() {
+            Some(tsig) => tsig,
+            None => return Ok(message),
+        };
+        let code = match *error {
+            TsigError::BadKey => RCODE::BadKey,
+            TsigError::BadSig => RCODE::BADSIG,
+            TsigError::BadTime => {
+                let mut server_time = vec![];
+                append_u48(&mut server_time, now);
+                let tsig = self.tsig(&message, request_tsig.time_signed, RCODE::BadTime, server_time)?;
+                let mut prior = vec![];
+                append_mac(&mut prior, &request_tsig.mac);
+                return self.append(message, &prior, true, tsig).map(|(message, _)| message);
+            },
+            _ => return Ok(message),
+        };
+
+        let tsig = Tsig {
+            key_name: request_tsig.key_name.clone(),
+            algorithm: request_tsig.algorithm.clone(),
+            time_signed: request_tsig.time_signed,
+            fudge: request_tsig.fudge,
+            mac: Cow::from(vec![]),
+            original_id: header(&message)?.0,
+            error: code,
+            other: Cow::from(vec![]),
+        };
+        append_tsig(message, &tsig)
+    }
+
+    /// A TSIG record for `message`, still without MAC.
+    fn tsig(&self, message: &[u8], time_signed: u64, error: RCODE, other: Vec<u8>) -> Result<'static, Tsig<'static>> {
+        Ok(Tsig {
+            key_name: self.name.clone(),
+            algorithm: self.algorithm.name(),
+            time_signed,
+            fudge: self.fudge,
+            mac: Cow::from(vec![]),
+            original_id: header(message)?.0,
+            error,
+            other: Cow::from(other),
+        })
+    }
+
+    /// Sign `message` and append `tsig` to it. The MAC also covers
+    /// `prefix`, i.e. the prior MAC and any unsigned messages since.
+    fn append(&self, message: Vec<u8>, prefix: &[u8], full: bool, mut tsig: Tsig<'static>) -> Result<'static, (Vec<u8>, Vec<u8>)> {
+        let variables = if full { tsig.variables() } else { tsig.timers() };
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(&message);
+        data.extend(variables);
+
+        let mac = self.algorithm.sign(&self.secret, &data);
+        tsig.mac = Cow::from(mac.clone());
+        Ok((append_tsig(message, &tsig)?, mac))
+    }
+
+    /// Verify the TSIG record of `data`, returning its MAC, or `None`
+    /// if the message isn't signed.
+    fn check(&self, data: &[u8], prefix: &[u8], full: bool, now: u64) -> result::Result<Option<Vec<u8>>, TsigError> {
+        let (message, index) = parse_signed(data)
+            .map_err(|e| TsigError::Malformed(e.into_owned()))?;
+        let (tsig, index) = match (message.tsig(), index) {
+            (Some(tsig), Some(index)) => (tsig, index),
+            _ => return Ok(None),
+        };
+
+        if tsig.key_name != self.name || tsig.algorithm != self.algorithm.name() {
+            return Err(TsigError::BadKey);
+        }
+        if tsig.mac.is_empty() && tsig.error != RCODE::Ok {
+            return Err(TsigError::Rejected(tsig.error));
+        }
+        let mac_len = self.algorithm.mac_len();
+        if tsig.mac.len() > mac_len || tsig.mac.len() < cmp::max(10, mac_len / 2) {
+            return Err(TsigError::Malformed(Error::new(
+                DnsMsgError::InvalidData,
+                format!("MAC of {} bytes is invalid for {}", tsig.mac.len(), tsig.algorithm)
+            )));
+        }
+
+        let mut signed = prefix.to_vec();
+        signed.extend(original_message(&data[..index], tsig.original_id).map_err(TsigError::Malformed)?);
+        signed.extend(if full { tsig.variables() } else { tsig.timers() });
+
+        if !self.algorithm.verify(&self.secret, &signed, &tsig.mac) {
+            return Err(TsigError::BadSig);
+        }
+        if now.abs_diff(tsig.time_signed) > u64::from(tsig.fudge) {
+            return Err(TsigError::BadTime);
+        }
+        if tsig.error != RCODE::Ok {
+            return Err(TsigError::Rejected(tsig.error));
+        }
+
+        Ok(Some(tsig.mac.to_vec()))
+    }
+}
+
+/// Signs or verifies the responses to a request. The first response
+/// covers the request's MAC, each later message of a TCP stream the MAC
+/// of the message before it.
+#[derive(Debug)]
+pub struct TsigSession<'k> {
+    key: &'k TsigKey,
+    mac: Vec<u8>,
+    first: bool,
+    unsigned: Vec<u8>,
+    unsigned_count: usize,
+}
+
+impl<'k> TsigSession<'k> {
+    fn new(key: &'k TsigKey, mac: Vec<u8>) -> Self {
+        TsigSession {
+            key,
+            mac,
+            first: true,
+            unsigned: vec![],
+            unsigned_count: 0,
+        }
+    }
+
+    /// Sign the next response.
+    pub fn sign(&mut self, message: Vec<u8>, now: u64) -> Result<'static, Vec<u8>> {
+        let tsig = self.key.tsig(&message, now, RCODE::Ok, vec![])?;
+        let mut prior = vec![];
+        append_mac(&mut prior, &self.mac);
+        let (message, mac) = self.key.append(message, &prior, self.first, tsig)?;
+        self.mac = mac;
+        self.first = false;
+        Ok(message)
+    }
+
+    /// Verify the next response. After the first one, up to 99 unsigned
+    /// messages are accepted, which are covered by the next signed one.
+    pub fn verify(&mut self, data: &[u8], now: u64) -> result::Result<(), TsigError> {
+        let mut prefix = vec![];
+        append_mac(&mut prefix, &self.mac);
+        prefix.extend_from_slice(&self.unsigned);
+
+        match self.key.check(data, &prefix, self.first, now)? {
+            Some(mac) => {
+                self.mac = mac;
+                self.first = false;
+                self.unsigned.clear();
+                self.unsigned_count = 0;
+                Ok(())
+            },
+            None if !self.first && self.unsigned_count < MAX_UNSIGNED_MESSAGES => {
+                self.unsigned.extend_from_slice(data);
+                self.unsigned_count += 1;
+                Ok(())
+            },
+            None => Err(TsigError::Unsigned),
+        }
+    }
+
+    /// Check that the last verified message of a TCP stream was signed.
+    pub fn finish(&self) -> result::Result<(), TsigError> {
+        if self.first || self.unsigned_count > 0 {
+            return Err(TsigError::Unsigned);
+        }
+        Ok(())
+    }
+}
+
+fn append_u48(buffer: &mut Vec<u8>, val: u64) {
+    append_u16(buffer, (val >> 32) as u16);
+    append_u32(buffer, val as u32);
+}
+
+fn append_mac(buffer: &mut Vec<u8>, mac: &[u8]) {
+    append_u16(buffer, mac.len() as u16);
+    buffer.extend_from_slice(mac);
+}
+
+/// A name in the lower case, uncompressed form covered by MACs.
+fn canonical(name: &Name) -> Vec<u8> {
+    let mut buffer = vec![];
+    for label in name.labels() {
+        buffer.push(label.len() as u8);
+        buffer.extend(label.iter().map(u8::to_ascii_lowercase));
+    }
+    buffer.push(0);
+    buffer
+}
+
+/// The ID and ARCOUNT of a message.
+fn header(message: &[u8]) -> Result<'static, (u16, u16)> {
+    if message.len() < 12 {
+        return Err(Error::new(
+            DnsMsgError::Truncated,
+            format!("Message of {} bytes is shorter than its header", message.len())
+        ));
+    }
+    Ok((u16::from(message[0]) << 8 | u16::from(message[1]), u16::from(message[10]) << 8 | u16::from(message[11])))
+}
+
+/// The message as it was before its TSIG record was added.
+fn original_message(message: &[u8], original_id: u16) -> Result<'static, Vec<u8>> {
+    let (_, arcount) = header(message)?;
+    let mut message = message.to_vec();
+    write_u16(&mut message, &mut 0, original_id);
+    write_u16(&mut message, &mut 10, arcount.saturating_sub(1));
+    Ok(message)
+}
+
+fn append_tsig(mut message: Vec<u8>, tsig: &Tsig) -> Result<'static, Vec<u8>> {
+    let (_, arcount) = header(&message)?;
+    let arcount = arcount.checked_add(1)
+        .ok_or(Error::new(DnsMsgError::InvalidData, "No room for a TSIG record in the additional section"))?;
+    write_u16(&mut message, &mut 10, arcount);
+    tsig.encode(&mut message);
+    Ok(message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::builder::{DnsMessageBuilder, Question};
+    use ::enums::{QR, Qtype, Qclass};
+    use ::parse;
+
+    const NOW: u64 = 1_500_000_000;
+
+    fn key() -> TsigKey {
+        TsigKey::new("KEY.example".parse().unwrap(), TsigAlgorithm::HmacSha256, b"secret")
+    }
+
+    fn query() -> Vec<u8> {
+        DnsMessageBuilder::new()
+            .with_id(0x1234)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN))
+            .build().unwrap()
+    }
+
+    fn response(rcode: RCODE) -> Vec<u8> {
+        DnsMessageBuilder::new()
+            .with_id(0x1234)
+            .with_qr(QR::RESPONSE)
+            .with_rcode(rcode)
+            .add_question(Question::new("example.com".parse().unwrap(), Qtype::A, Qclass::IN))
+            .build().unwrap()
+    }
+
+    #[test]
+    fn should_sign_requests() {
+        let (request, _) = key().sign_request(query(), NOW).unwrap();
+
+        let message = parse(&request).unwrap();
+        assert!(message.additionals().is_empty());
+        let tsig = message.tsig().unwrap();
+        assert_eq!(key().name(), &tsig.key_name);
+        assert_eq!("hmac-sha256.", tsig.algorithm.to_string());
+        assert_eq!(NOW, tsig.time_signed);
+        assert_eq!(300, tsig.fudge);
+        assert_eq!(0x1234, tsig.original_id);
+        // HMAC-SHA256 over the query and the TSIG variables, using the
+        // lower case key name
+        assert_eq!(
+            &[
+                0x38, 0xa2, 0xa1, 0x5a, 0x28, 0x23, 0xcc, 0x57, 0x0f, 0xbd, 0xc1, 0x92, 0x83, 0x5b, 0xaf, 0x7b,
+                0xe8, 0x9b, 0x0c, 0xaf, 0x20, 0x9c, 0x90, 0x3c, 0x24, 0x21, 0x27, 0xa9, 0xa6, 0xf5, 0xa8, 0xe5,
+            ][..],
+            &tsig.mac[..]
+        );
+        assert_eq!(request, message.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn should_verify_requests_and_responses() {
+        let key = key().with_fudge(60);
+        let (request, mut client) = key.sign_request(query(), NOW).unwrap();
+
+        let mut server = key.verify_request(&request, NOW + 60).unwrap();
+        let reply = server.sign(response(RCODE::Ok), NOW + 60).unwrap();
+
+        assert_eq!(Ok(()), client.verify(&reply, NOW));
+        assert_eq!(Ok(()), client.finish());
+    }
+
+    #[test]
+    fn should_verify_sha512() {
+        let sha512 = TsigKey::new("key.example".parse().unwrap(), TsigAlgorithm::HmacSha512, b"secret");
+        let (request, _) = sha512.sign_request(query(), NOW).unwrap();
+        assert_eq!(64, parse(&request).unwrap().tsig().unwrap().mac.len());
+        assert!(sha512.verify_request(&request, NOW).is_ok());
+        assert_eq!(Some(TsigError::BadKey), key().verify_request(&request, NOW).err());
+    }
+
+    #[test]
+    fn should_reject_tampered_and_unsigned_requests() {
+        let (mut request, _) = key().sign_request(query(), NOW).unwrap();
+        request[13] = b'E';
+        assert_eq!(Some(TsigError::BadSig), key().verify_request(&request, NOW).err());
+
+        assert_eq!(Some(TsigError::Unsigned), key().verify_request(&query(), NOW).err());
+    }
+
+    #[test]
+    fn should_signal_bad_signatures_and_keys() {
+        let other = TsigKey::new("other.example".parse().unwrap(), TsigAlgorithm::HmacSha256, b"secret");
+        let (request, mut client) = other.sign_request(query(), NOW).unwrap();
+
+        let error = key().verify_request(&request, NOW).unwrap_err();
+        assert_eq!(TsigError::BadKey, error);
+        let reply = key().error_response(response(RCODE::NotAuth), &parse(&request).unwrap(), &error, NOW).unwrap();
+
+        let message = parse(&reply).unwrap();
+        assert_eq!(RCODE::NotAuth, message.rcode());
+        assert_eq!(RCODE::BadKey, message.tsig().unwrap().error);
+        assert!(message.tsig().unwrap().mac.is_empty());
+        assert_eq!(Err(TsigError::Rejected(RCODE::BadKey)), client.verify(&reply, NOW));
+
+        let wrong_secret = TsigKey::new("KEY.example".parse().unwrap(), TsigAlgorithm::HmacSha256, b"guess");
+        let (request, mut client) = wrong_secret.sign_request(query(), NOW).unwrap();
+        let error = key().verify_request(&request, NOW).unwrap_err();
+        assert_eq!(TsigError::BadSig, error);
+        let reply = key().error_response(response(RCODE::NotAuth), &parse(&request).unwrap(), &error, NOW).unwrap();
+        assert_eq!(Err(TsigError::Rejected(RCODE::BADSIG)), client.verify(&reply, NOW));
+    }
+
+    #[test]
+    fn should_signal_bad_time() {
+        let key = key();
+        let (request, mut client) = key.sign_request(query(), NOW).unwrap();
+
+        let error = key.verify_request(&request, NOW + 301).unwrap_err();
+        assert_eq!(TsigError::BadTime, error);
+        let reply = key.error_response(response(RCODE::NotAuth), &parse(&request).unwrap(), &error, NOW + 301).unwrap();
+
+        let message = parse(&reply).unwrap();
+        let tsig = message.tsig().unwrap();
+        assert_eq!(NOW, tsig.time_signed);
+        assert_eq!(&[0, 0, 0x59, 0x68, 0x30, 0x2d][..], &tsig.other[..]);
+        // the response is signed, so the client can trust the error
+        assert_eq!(Err(TsigError::Rejected(RCODE::BadTime)), client.verify(&reply, NOW));
+
+        let mut tampered = reply.clone();
+        tampered[3] = 0;
+        assert_eq!(Err(TsigError::BadSig), client.verify(&tampered, NOW));
+    }
+
+    #[test]
+    fn should_verify_tcp_streams() {
+        let key = key();
+        let (request, mut client) = key.sign_request(query(), NOW).unwrap();
+        let mut server = key.verify_request(&request, NOW).unwrap();
+
+        let first = server.sign(response(RCODE::Ok), NOW).unwrap();
+        let second = server.sign(response(RCODE::Ok), NOW + 1).unwrap();
+        // servers may leave messages unsigned, which are covered by the
+        // next signed one
+        let unsigned = response(RCODE::Ok);
+        let mut prefix = vec![];
+        append_mac(&mut prefix, &server.mac);
+        prefix.extend_from_slice(&unsigned);
+        let tsig = key.tsig(&unsigned, NOW + 2, RCODE::Ok, vec![]).unwrap();
+        let last = key.append(response(RCODE::Ok), &prefix, false, tsig).unwrap().0;
+
+        assert_eq!(Err(TsigError::BadSig), client.verify(&second, NOW));
+        assert_eq!(Ok(()), client.verify(&first, NOW));
+        assert_eq!(Ok(()), client.verify(&second, NOW));
+        assert_eq!(Ok(()), client.verify(&unsigned, NOW));
+        assert_eq!(Err(TsigError::Unsigned), client.finish());
+        assert_eq!(Ok(()), client.verify(&last, NOW));
+        assert_eq!(Ok(()), client.finish());
+    }
+
+    #[test]
+    fn should_fail_on_messages_shorter_than_the_header() {
+        let expected = || Error::new(DnsMsgError::Truncated, "Message of 4 bytes is shorter than its header");
+        assert_eq!(Some(expected()), key().sign_request(vec![0u8; 4], NOW).err());
+
+        let key = key();
+        let (request, _) = key.sign_request(query(), NOW).unwrap();
+        let mut server = key.verify_request(&request, NOW).unwrap();
+        assert_eq!(Err(expected()), server.sign(vec![0u8; 4], NOW));
+        assert_eq!(Err(expected()), key.error_response(vec![0u8; 4], &parse(&request).unwrap(), &TsigError::BadKey, NOW));
+    }
+
+    #[test]
+    fn should_require_tsig_to_be_the_last_record() {
+        let (mut request, _) = key().sign_request(query(), NOW).unwrap();
+        request[11] += 1;
+        request.extend(&[0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 4, 192, 0, 2, 1]);
+
+        let index = query().len();
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, format!("TSIG record at index {} is not the last record", index))),
+            parse(&request)
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use std::borrow::Cow;
+use ::builder::{DnsMessageBuilder, Question};
+use ::dns_record::{DnsRecord, Record, RecordPayload};
+use ::edns::Edns;
+use ::enums::*;
+use ::errors::*;
+use ::name::Name;
+
+/// UPDATE messages are usually sent over TCP, so they are only limited
+/// by the message length field.
+const MAX_MESSAGE_SIZE: usize = 65535;
+
+/// A condition the zone has to meet before an UPDATE is applied
+/// (RFC 2136, section 2.4).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prerequisite<'a> {
+    /// An RRset of the type exists at the name.
+    RRsetExists(Name<'a>, Type),
+    /// A record of an RRset which has to exist with exactly the records
+    /// given by all such prerequisites for the name and type.
+    RRsetExistsWithData(Name<'a>, RecordPayload<'a>),
+    RRsetDoesNotExist(Name<'a>, Type),
+    /// The name owns at least one record.
+    NameInUse(Name<'a>),
+    NameNotInUse(Name<'a>),
+}
+
+/// A change to the zone (RFC 2136, section 2.5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateOperation<'a> {
+    /// Add the record to its RRset. The record's class is replaced by
+    /// the zone's class.
+    Add(Record<'a>),
+    DeleteRRset(Name<'a>, Type),
+    /// Delete all RRsets owned by the name.
+    DeleteName(Name<'a>),
+    /// Delete a single record from its RRset.
+    Delete(Name<'a>, RecordPayload<'a>),
+}
+
+/// Builds UPDATE messages for a zone. Unlike `DnsMessageBuilder`, it
+/// never leaves records out to stay within a UDP payload size.
+#[derive(Debug)]
+pub struct UpdateBuilder<'a> {
+    builder: DnsMessageBuilder<'a>,
+    class: Class,
+}
+
+impl<'a> UpdateBuilder<'a> {
+    pub fn new(zone: Name<'a>, class: Class) -> Self {
+        let builder = DnsMessageBuilder::new()
+            .with_opcode(OPCODE::UPDATE)
+            .with_rd(false)
+            .with_max_size(MAX_MESSAGE_SIZE)
+            .add_question(Question::new(zone, Qtype::SOA, Qclass::from(u16::from(class))));
+        UpdateBuilder { builder, class }
+    }
+
+    pub fn with_id(mut self, id: u16) -> Self {
+        self.builder = self.builder.with_id(id);
+        self
+    }
+
+    pub fn with_edns(mut self, edns: Edns<'a>) -> Self {
+        self.builder = self.builder.with_edns(edns);
+        self
+    }
+
+    pub fn require(mut self, prerequisite: Prerequisite<'a>) -> Self {
+        let record = prerequisite.into_record(self.class);
+        self.builder = self.builder.add_answer(record);
+        self
+    }
+
+    pub fn update(mut self, operation: UpdateOperation<'a>) -> Self {
+        let record = operation.into_record(self.class);
+        self.builder = self.builder.add_authority(record);
+        self
+    }
+
+    pub fn add_additional(mut self, record: Record<'a>) -> Self {
+        self.builder = self.builder.add_additional(record);
+        self
+    }
+
+    pub fn build(self) -> Result<'static, Vec<u8>> {
+        self.builder.build()
+    }
+}
+
+/// The zone, prerequisites and updates of a parsed UPDATE message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateMessage<'a> {
+    pub zone: Name<'a>,
+    pub class: Class,
+    pub prerequisites: Vec<Prerequisite<'a>>,
+    pub updates: Vec<UpdateOperation<'a>>,
+}
+
+impl<'a> UpdateMessage<'a> {
+    /// Decode the sections of an UPDATE message. Fails on anything a
+    /// server would answer with FORMERR (RFC 2136, section 3).
+    pub fn from_message(message: &DnsRecord<'a>) -> Result<'static, Self> {
+        if message.opcode() != OPCODE::UPDATE {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("Message is not an UPDATE but {}", message.opcode())));
+        }
+        if message.zone().len() != 1 {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("UPDATE needs exactly one zone, not {}", message.zone().len())));
+        }
+        let zone = &message.zone()[0];
+        if zone.qtype != Qtype::SOA {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("Zone of type {} is not SOA", zone.qtype)));
+        }
+        let class = Class::from(u16::from(zone.qclass));
+
+        let prerequisites = message.prerequisites().iter()
+            .enumerate()
+            .map(|(index, record)| Prerequisite::from_record(record, class, index))
+            .collect::<Result<'static, _>>()?;
+        let updates = message.updates().iter()
+            .enumerate()
+            .map(|(index, record)| UpdateOperation::from_record(record, class, index))
+            .collect::<Result<'static, _>>()?;
+
+        Ok(UpdateMessage {
+            zone: zone.name.clone(),
+            class,
+            prerequisites,
+            updates,
+        })
+    }
+}
+
+impl<'a> Prerequisite<'a> {
+    fn into_record(self, class: Class) -> Record<'a> {
+        match self {
+            Prerequisite::RRsetExists(name, typ) => Record::new(name, Class::Wildcard, 0, empty(typ)),
+            Prerequisite::RRsetExistsWithData(name, payload) => Record::new(name, class, 0, payload),
+            Prerequisite::RRsetDoesNotExist(name, typ) => Record::new(name, Class::NONE, 0, empty(typ)),
+            Prerequisite::NameInUse(name) => Record::new(name, Class::Wildcard, 0, empty(Type::Wildcard)),
+            Prerequisite::NameNotInUse(name) => Record::new(name, Class::NONE, 0, empty(Type::Wildcard)),
+        }
+    }
+
+    fn from_record(record: &Record<'a>, class: Class, index: usize) -> Result<'static, Self> {
+        if record.ttl != 0 {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("Prerequisite at index {} has a TTL of {}", index, record.ttl)));
+        }
+        let name = record.name.clone();
+        let typ = record.payload.typ();
+
+        match record.class {
+            Class::Wildcard | Class::NONE if !is_empty(&record.payload) => {
+                Err(Error::new(DnsMsgError::InvalidData, format!("Prerequisite at index {} of class {} has data", index, record.class)))
+            },
+            Class::Wildcard if typ == Type::Wildcard => Ok(Prerequisite::NameInUse(name)),
+            Class::Wildcard => Ok(Prerequisite::RRsetExists(name, typ)),
+            Class::NONE if typ == Type::Wildcard => Ok(Prerequisite::NameNotInUse(name)),
+            Class::NONE => Ok(Prerequisite::RRsetDoesNotExist(name, typ)),
+            c if c == class => Ok(Prerequisite::RRsetExistsWithData(name, record.payload.clone())),
+            c => Err(Error::new(DnsMsgError::InvalidData, format!("Prerequisite at index {} has class {}", index, c))),
+        }
+    }
+}
+
+impl<'a> UpdateOperation<'a> {
+    fn into_record(self, class: Class) -> Record<'a> {
+        match self {
+            UpdateOperation::Add(record) => Record { class, ..record },
+            UpdateOperation::DeleteRRset(name, typ) => Record::new(name, Class::Wildcard, 0, empty(typ)),
+            UpdateOperation::DeleteName(name) => Record::new(name, Class::Wildcard, 0, empty(Type::Wildcard)),
+            UpdateOperation::Delete(name, payload) => Record::new(name, Class::NONE, 0, payload),
+        }
+    }
+
+    fn from_record(record: &Record<'a>, class: Class, index: usize) -> Result<'static, Self> {
+        let name = record.name.clone();
+        let typ = record.payload.typ();
+
+        if record.class == class {
+            return match typ {
+                Type::Wildcard | Type::OPT => {
+                    Err(Error::new(DnsMsgError::InvalidData, format!("Update at index {} adds a record of type {}", index, typ)))
+                },
+                _ => Ok(UpdateOperation::Add(record.clone())),
+            };
+        }
+        if record.ttl != 0 {
+            return Err(Error::new(DnsMsgError::InvalidData, format!("Update at index {} deletes with a TTL of {}", index, record.ttl)));
+        }
+
+        match record.class {
+            Class::Wildcard if !is_empty(&record.payload) => {
+                Err(Error::new(DnsMsgError::InvalidData, format!("Update at index {} of class ANY has data", index)))
+            },
+            Class::Wildcard if typ == Type::Wildcard => Ok(UpdateOperation::DeleteName(name)),
+            Class::Wildcard => Ok(UpdateOperation::DeleteRRset(name, typ)),
+            Class::NONE if is_empty(&record.payload) || typ == Type::Wildcard => {
+                Err(Error::new(DnsMsgError::InvalidData, format!("Update at index {} of class NONE has no record to delete", index)))
+            },
+            Class::NONE => Ok(UpdateOperation::Delete(name, record.payload.clone())),
+            c => Err(Error::new(DnsMsgError::InvalidData, format!("Update at index {} has class {}", index, c))),
+        }
+    }
+}
+
+/// The payload of a record which refers to a whole RRset.
+fn empty<'a>(typ: Type) -> RecordPayload<'a> {
+    RecordPayload::Unknown(u16::from(typ), Cow::from(&[][..]))
+}
+
+fn is_empty(payload: &RecordPayload) -> bool {
+    match *payload {
+        RecordPayload::Unknown(_, ref data) => data.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use ::parse;
+
+    fn name(name: &str) -> Name<'static> {
+        name.parse().unwrap()
+    }
+
+    fn a(address: &str) -> RecordPayload<'static> {
+        RecordPayload::A(address.parse().unwrap())
+    }
+
+    #[test]
+    fn should_build_and_decode_updates() {
+        let prerequisites = vec![
+            Prerequisite::RRsetExists(name("example.com"), Type::SOA),
+            Prerequisite::RRsetExistsWithData(name("ns.example.com"), a("192.0.2.53")),
+            Prerequisite::RRsetDoesNotExist(name("www.example.com"), Type::CNAME),
+            Prerequisite::NameInUse(name("mail.example.com")),
+            Prerequisite::NameNotInUse(name("new.example.com")),
+        ];
+        let updates = vec![
+            UpdateOperation::Add(Record::new(name("new.example.com"), Class::IN, 300, a("192.0.2.1"))),
+            UpdateOperation::DeleteRRset(name("www.example.com"), Type::A),
+            UpdateOperation::DeleteName(name("old.example.com")),
+            UpdateOperation::Delete(name("mail.example.com"), a("192.0.2.25")),
+        ];
+
+        let builder = UpdateBuilder::new(name("example.com"), Class::IN);
+        let builder = prerequisites.iter().cloned().fold(builder, UpdateBuilder::require);
+        let builder = updates.iter().cloned().fold(builder, UpdateBuilder::update);
+        let buffer = builder.build().unwrap();
+
+        let message = parse(&buffer).unwrap();
+        assert_eq!(OPCODE::UPDATE, message.opcode());
+        assert!(!message.rd());
+        assert_eq!(
+            UpdateMessage {
+                zone: name("example.com"),
+                class: Class::IN,
+                prerequisites,
+                updates,
+            },
+            UpdateMessage::from_message(&message).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_use_class_any_and_none_on_the_wire() {
+        let buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .update(UpdateOperation::DeleteRRset(name("example.com"), Type::MX))
+            .build().unwrap();
+
+        // the owner name is compressed against the zone
+        let record = 12 + 13 + 4;
+        assert_eq!(
+            &buffer[record..],
+            &[0xc0, 12, 0, 15, 0, 255, 0, 0, 0, 0, 0, 0]
+        );
+
+        let buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .update(UpdateOperation::Delete(name("example.com"), a("192.0.2.1")))
+            .build().unwrap();
+        assert_eq!(
+            &buffer[record..],
+            &[0xc0, 12, 0, 1, 0, 254, 0, 0, 0, 0, 0, 4, 192, 0, 2, 1]
+        );
+    }
+
+    #[test]
+    fn should_add_records_in_the_zone_class() {
+        let record = Record::new(name("www.example.com"), Class::CH, 60, RecordPayload::A(Ipv4Addr::new(192, 0, 2, 1)));
+        let buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .update(UpdateOperation::Add(record))
+            .build().unwrap();
+
+        let message = parse(&buffer).unwrap();
+        assert_eq!(Class::IN, message.updates()[0].class);
+    }
+
+    #[test]
+    fn should_not_truncate_updates() {
+        let builder = UpdateBuilder::new(name("example.com"), Class::IN);
+        let builder = (0..100)
+            .map(|i| UpdateOperation::Add(Record::new(name("www.example.com"), Class::IN, 300, RecordPayload::A(Ipv4Addr::new(192, 0, 2, i)))))
+            .fold(builder, UpdateBuilder::update);
+
+        let message = parse(&builder.build().unwrap()).unwrap().into_owned();
+        assert!(!message.tc());
+        assert_eq!(100, message.updates().len());
+    }
+
+    #[test]
+    fn should_reject_malformed_updates() {
+        let query = ::builder::DnsMessageBuilder::new().build().unwrap();
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Message is not an UPDATE but QUERY")),
+            UpdateMessage::from_message(&parse(&query).unwrap())
+        );
+
+        let mut buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .require(Prerequisite::RRsetExistsWithData(name("example.com"), a("192.0.2.1")))
+            .build().unwrap();
+        // turn the prerequisite into one of class ANY with data
+        let class = 12 + 13 + 4 + 4;
+        buffer[class..class + 2].copy_from_slice(&[0, 255]);
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Prerequisite at index 0 of class ANY has data")),
+            UpdateMessage::from_message(&parse(&buffer).unwrap())
+        );
+
+        let mut buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .require(Prerequisite::NameInUse(name("example.com")))
+            .build().unwrap();
+        let ttl = class + 2;
+        buffer[ttl + 3] = 1;
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Prerequisite at index 0 has a TTL of 1")),
+            UpdateMessage::from_message(&parse(&buffer).unwrap())
+        );
+
+        let mut buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .update(UpdateOperation::DeleteName(name("example.com")))
+            .build().unwrap();
+        buffer[class..class + 2].copy_from_slice(&[0, 254]);
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Update at index 0 of class NONE has no record to delete")),
+            UpdateMessage::from_message(&parse(&buffer).unwrap())
+        );
+    }
+
+    #[test]
+    fn should_only_accept_empty_rdata_in_updates() {
+        let mut buffer = UpdateBuilder::new(name("example.com"), Class::IN)
+            .update(UpdateOperation::DeleteRRset(name("www.example.com"), Type::A))
+            .build().unwrap();
+        assert!(parse(&buffer).is_ok());
+
+        // the same record in a QUERY is an A record without an address
+        buffer[2] &= 0b_1000_0111;
+        assert_eq!(
+            Err(Error::new(DnsMsgError::InvalidData, "Length of 0 is invalid for type A")),
+            parse(&buffer)
+        );
+    }
+}
@@ -1,33 +1,22 @@
 pub fn write_u16(target: &mut[u8], pos: &mut usize, val: u16) {
-    assert!(*pos < target.len() - 2, "array index out of bounds!");
+    assert!(*pos + 2 <= target.len(), "array index out of bounds!");
 
-    let tmp = &target[*pos] as *const u8;
-    let tmp = tmp as *mut u16;
-    unsafe {
-        *tmp = val.to_be();
-    }
+    target[*pos..*pos + 2].copy_from_slice(&val.to_be_bytes());
     *pos += 2;
 }
 
+#[cfg(test)]
 pub fn write_u32(target: &mut[u8], pos: &mut usize, val: u32) {
-    assert!(*pos < target.len() - 4, "array index out of bounds!");
+    assert!(*pos + 4 <= target.len(), "array index out of bounds!");
 
-    let tmp = &target[*pos] as *const u8;
-    let tmp = tmp as *mut u32;
-    unsafe {
-        *tmp = val.to_be();
-    }
+    target[*pos..*pos + 4].copy_from_slice(&val.to_be_bytes());
     *pos += 4;
 }
 
 pub fn append_u16(buffer: &mut Vec<u8>, val: u16) {
-    let index = buffer.len();
-    buffer.push(0);
-    buffer.push(0);
+    buffer.extend_from_slice(&val.to_be_bytes());
+}
 
-    let u8ptr = &buffer[index] as *const u8;
-    let u16ptr = u8ptr as *mut u16;
-    unsafe {
-        *u16ptr = val.to_be();
-    }
+pub fn append_u32(buffer: &mut Vec<u8>, val: u32) {
+    buffer.extend_from_slice(&val.to_be_bytes());
 }
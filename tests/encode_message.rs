@@ -0,0 +1,39 @@
+extern crate dns;
+
+use dns::*;
+use std::fs::File;
+use std::io::Read;
+
+const CAPTURES: &[&str] = &["tests/dnsquery.txt", "tests/response_packet.txt",
+    "tests/dnssec_response.txt"];
+
+#[test]
+fn captures_should_survive_parse_encode_parse() {
+    for path in CAPTURES {
+        let mut bytes = vec![];
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+
+        let message = parse(&bytes).unwrap();
+        let encoded = message.to_bytes().unwrap();
+        assert_eq!(message, parse(&encoded).unwrap(), "{}", path);
+        assert_eq!(encoded, parse(&encoded).unwrap().to_bytes().unwrap(), "{}", path);
+    }
+}
+
+#[test]
+fn mutated_captures_should_survive_parse_encode_parse() {
+    for path in CAPTURES {
+        let mut bytes = vec![];
+        File::open(path).unwrap().read_to_end(&mut bytes).unwrap();
+
+        for index in 0..bytes.len() {
+            for value in 0..=255u8 {
+                let mut mutated = bytes.clone();
+                mutated[index] = value;
+                if let Ok(message) = parse(&mutated) {
+                    assert_eq!(message, parse(&message.to_bytes().unwrap()).unwrap(), "{} at {}", path, index);
+                }
+            }
+        }
+    }
+}
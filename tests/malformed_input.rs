@@ -0,0 +1,58 @@
+extern crate dns;
+
+use dns::*;
+use std::fs::File;
+use std::io::Read;
+
+const CAPTURES: &[&str] = &[
+    "tests/dnsquery.txt",
+    "tests/response_packet.txt",
+    "tests/dnssec_response.txt",
+];
+
+fn read_capture(path: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut file = File::open(path).unwrap();
+    file.read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn captures_should_parse() {
+    for path in CAPTURES {
+        let bytes = read_capture(path);
+        assert!(parse(&bytes).is_ok(), "{} should parse", path);
+    }
+}
+
+#[test]
+fn every_truncation_should_fail_with_truncated() {
+    for path in CAPTURES {
+        let bytes = read_capture(path);
+        for len in 0..bytes.len() {
+            let result = parse(&bytes[..len]);
+            assert_eq!(
+                Some(DnsMsgError::Truncated),
+                result.err().map(|e| e.kind),
+                "{} truncated to {} bytes",
+                path,
+                len
+            );
+        }
+    }
+}
+
+#[test]
+fn every_single_byte_mutation_should_not_panic() {
+    for path in CAPTURES {
+        let mut bytes = read_capture(path);
+        for pos in 0..bytes.len() {
+            let original = bytes[pos];
+            for val in 0..=255u8 {
+                bytes[pos] = val;
+                let _ = parse(&bytes);
+            }
+            bytes[pos] = original;
+        }
+    }
+}
@@ -0,0 +1,58 @@
+extern crate dns;
+
+use dns::*;
+use std::fs::File;
+use std::io::Read;
+
+fn read(path: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut file = File::open(path).unwrap();
+    file.read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+/// The validated response as a resolver would send it for a query with
+/// CD set: the same records, with CD instead of AD. This is synthetic,
+/// derived from the captured response rather than captured itself.
+fn checking_disabled_response() -> Vec<u8> {
+    let mut bytes = read("tests/dnssec_response.txt");
+    bytes[3] = (bytes[3] & !0b_0010_0000) | 0b_0001_0000;
+    bytes
+}
+
+#[test]
+fn read_validated_response() {
+    let bytes = read("tests/dnssec_response.txt");
+
+    let result = parse(&bytes).unwrap();
+    assert_eq!(QR::RESPONSE, result.qr());
+    assert!(result.rd());
+    assert!(result.ra());
+    assert!(!result.z());
+    assert!(result.ad());
+    assert!(!result.cd());
+    assert_eq!(RCODE::Ok, result.rcode());
+    assert!(result.edns().unwrap().dnssec_ok);
+    assert_eq!(Type::Unknown(46), result.answers()[1].payload.typ());
+}
+
+#[test]
+fn read_checking_disabled_response() {
+    let bytes = checking_disabled_response();
+
+    let result = parse(&bytes).unwrap();
+    assert!(result.ra());
+    assert!(!result.z());
+    assert!(!result.ad());
+    assert!(result.cd());
+    assert_eq!(RCODE::Ok, result.rcode());
+    assert_eq!(2, result.answers().len());
+}
+
+#[test]
+fn keep_dnssec_flags_when_encoding() {
+    for bytes in [read("tests/dnssec_response.txt"), checking_disabled_response()] {
+        let result = parse(&bytes).unwrap();
+        assert_eq!(bytes, result.to_bytes().unwrap());
+    }
+}
@@ -6,10 +6,10 @@ use std::io::Read;
 
 #[test]
 fn check_header() {
-    let mut buffer = [0u8; 512];
+    let mut buffer = vec![];
     {
         let mut file = File::open("tests/dnsquery.txt").unwrap();
-        file.read(&mut buffer).unwrap();
+        file.read_to_end(&mut buffer).unwrap();
     }
 
     let result = parse(&buffer).unwrap();
@@ -17,5 +17,8 @@ fn check_header() {
     assert_eq!(QR::QUERY, result.qr());
     assert_eq!(OPCODE::QUERY, result.opcode());
     assert!(result.rd());
+    assert!(result.ad());
+    assert!(!result.cd());
+    assert!(!result.z());
     assert_eq!(1, result.questions().len());
 }
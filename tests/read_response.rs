@@ -16,7 +16,34 @@ fn read_response() {
         result.answers(),
         &[
             dns::Record::new(
-                vec!["google", "com"],
+                "google.com".parse().unwrap(),
+                Class::IN,
+                299,
+                dns::RecordPayload::A(Ipv4Addr::new(172, 217, 22, 78))
+            )
+        ]
+    );
+}
+
+fn parse_owned(path: &str) -> dns::Result<'static, DnsRecord<'static>> {
+    let mut bytes = vec![];
+    let mut file = File::open(path).unwrap();
+    file.read_to_end(&mut bytes).unwrap();
+
+    parse(&bytes)
+        .map(|record| record.into_owned())
+        .map_err(|e| e.into_owned())
+}
+
+#[test]
+fn read_response_into_owned() {
+    let result = parse_owned("tests/response_packet.txt").unwrap();
+    assert_eq!("google.com.", result.questions()[0].name.to_string());
+    assert_eq!(
+        result.answers(),
+        &[
+            dns::Record::new(
+                "google.com".parse().unwrap(),
                 Class::IN,
                 299,
                 dns::RecordPayload::A(Ipv4Addr::new(172, 217, 22, 78))